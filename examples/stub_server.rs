@@ -0,0 +1,31 @@
+//! Serves the Todoist fixtures on a local port so `tot` can be run without network.
+//!
+//! ```bash
+//! cargo run --example stub_server -- 127.0.0.1:8080 fixtures
+//! TOT_TODOIST_URL=http://127.0.0.1:8080 cargo run
+//! ```
+
+use std::path::PathBuf;
+use std::thread;
+
+#[allow(dead_code)]
+#[path = "../src/stub_server.rs"]
+mod stub_server;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let address = args
+        .next()
+        .unwrap_or_else(|| String::from("127.0.0.1:8080"));
+    let fixtures = args
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(stub_server::default_fixtures);
+
+    let server = stub_server::StubServer::bind(&address, &fixtures).unwrap();
+    println!("Serving {} on {}", fixtures.display(), server.url);
+
+    loop {
+        thread::park();
+    }
+}
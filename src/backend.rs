@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::ics::Ics;
+use crate::items::Item;
+use crate::markdown::Markdown;
+use crate::snoozed;
+use crate::time::{self, Snooze};
+use crate::todo_txt::TodoTxt;
+use crate::todoist::Todoist;
+
+/// Where items come from and where changes to them go, ranking and the GUI only see items
+/// through this
+pub trait TaskBackend {
+    /// Bring the cached items up to date, and those of the view when one is given
+    fn refresh(&self, config: &Config, view: Option<&str>) -> Result<(), Error>;
+
+    /// Names of the projects and other views that can be picked, sorted
+    fn views(&self, config: &Config) -> Result<Vec<String>, Error>;
+
+    /// Open items in the view as of the last refresh, without the network
+    fn items(&self, config: &Config, view: &str) -> Result<Vec<Item>, Error>;
+
+    /// Whether there are items to answer from before the first refresh
+    fn has_items(&self, _config: &Config) -> bool {
+        true
+    }
+
+    /// When the source last changed, for backends that can be watched for changes made elsewhere
+    fn modified(&self, _config: &Config) -> Option<SystemTime> {
+        None
+    }
+
+    fn complete(&self, config: &Config, id: &str) -> Result<(), Error>;
+
+    fn uncomplete(&self, config: &Config, id: &str) -> Result<(), Error>;
+
+    /// Move the item to a date or datetime, recurring items stay recurring
+    fn update_due(&self, config: &Config, id: &str, date: &str) -> Result<(), Error>;
+
+    /// Add an item to the view from text typed in the GUI
+    fn add(&self, config: &Config, view: &str, text: &str) -> Result<(), Error>;
+
+    /// Text to show in the GUI as an example of what add understands
+    fn add_example(&self) -> &'static str {
+        "Call Bob"
+    }
+
+    /// The text shown for an item, backends can add context such as its parent
+    fn fmt_item(&self, config: &Config, item: &Item, _view: &str) -> Result<String, Error> {
+        Ok(item.fmt(config, None, None))
+    }
+}
+
+/// The backends that can be picked in config
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    #[default]
+    Todoist,
+    /// A todo.txt file, i.e. { "todo_txt": { "path": "/home/me/todo.txt" } }
+    TodoTxt { path: String },
+    /// A directory of Markdown files with checklists, i.e. { "markdown": { "directory": "/home/me/notes" } }
+    Markdown { directory: String },
+    /// A directory of .ics files with VTODOs, i.e. { "ics": { "directory": "/home/me/calendars" } }
+    Ics { directory: String },
+}
+
+impl Backend {
+    pub fn task_backend(&self) -> Box<dyn TaskBackend> {
+        match self {
+            Backend::Todoist => Box::new(Todoist),
+            Backend::TodoTxt { path } => Box::new(TodoTxt { path: path.clone() }),
+            Backend::Markdown { directory } => Box::new(Markdown {
+                directory: directory.clone(),
+            }),
+            Backend::Ics { directory } => Box::new(Ics {
+                directory: directory.clone(),
+            }),
+        }
+    }
+}
+
+/// Complete the last item returned by "next item"
+pub fn complete_item(config: Config) -> Result<String, Error> {
+    if let Some(id) = &config.next_id {
+        config.backend.task_backend().complete(&config, id)?;
+    }
+
+    if !cfg!(test) {
+        config.clear_next_id().save()?;
+    }
+
+    // Does not pass back an item
+    Ok(String::from("✓"))
+}
+
+/// Undo the completion of an item
+pub fn uncomplete_item(config: Config, id: &str) -> Result<String, Error> {
+    config.backend.task_backend().uncomplete(&config, id)?;
+
+    Ok(String::from("↶"))
+}
+
+/// Move the due date of the last item returned by "next item"
+pub fn snooze_item(config: Config, snooze: Snooze) -> Result<String, Error> {
+    if let Some(id) = &config.next_id {
+        let date = snooze.due_string(time::now(&config));
+        config
+            .backend
+            .task_backend()
+            .update_due(&config, id, &date)?;
+        // Still due today after an hour or until tonight
+        if date.contains('T') {
+            snoozed::add(&config, id, &date)?;
+        }
+    }
+
+    Ok(String::from("✓"))
+}
+
+/// Add an item to the view
+pub fn add_item(config: Config, view: &str, text: &str) -> Result<String, Error> {
+    config.backend.task_backend().add(&config, view, text)?;
+
+    Ok(String::from("✓"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub_server::{self, StubServer};
+    use crate::test;
+    use crate::{cache, projects};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn snooze_item_moves_the_shown_item() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        cache::refresh(&config).unwrap();
+        let config = config.set_next_id(String::from("6543210001"));
+
+        assert_eq!(
+            snooze_item(config.clone(), Snooze::NextWeek),
+            Ok(String::from("✓"))
+        );
+
+        let next_week = Snooze::NextWeek.due_string(time::now(&config));
+        let items = Todoist.items(&config, "Home").unwrap();
+        let snoozed = items.iter().find(|item| item.id == "6543210001").unwrap();
+        assert_eq!(
+            snoozed.due.as_ref().map(|due| due.date.clone()),
+            Some(next_week)
+        );
+        assert!(server.requests()[1].body.contains("\"item_update\""));
+    }
+
+    #[test]
+    fn snoozing_for_an_hour_moves_on_to_the_next_item() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let text = projects::next_item(config.clone(), "Home", &[]).unwrap();
+        assert!(text.unwrap().contains("Pay the electricity bill"));
+
+        let config = Config::load(&config.path).unwrap();
+        snooze_item(config.clone(), Snooze::OneHour).unwrap();
+
+        let text = projects::cached_next_item(config, "Home", &[]).unwrap();
+        assert_eq!(text, Some(String::from("\nWater the plants")));
+    }
+}
@@ -0,0 +1,377 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::items::Item;
+use crate::projects::{Project, Section};
+use crate::request;
+use crate::request::SyncResponse;
+use crate::store;
+
+const FILENAME: &str = "tot_cache.json";
+
+/// Asks Todoist for everything instead of the changes since the last sync
+const FULL_SYNC_TOKEN: &str = "*";
+
+/// How many completed items to keep, so that their completion can be undone
+const CLOSED_LIMIT: usize = 10;
+
+/// Open items, active projects and their sections, kept up to date with incremental syncs
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Cache {
+    pub sync_token: String,
+    pub items: Vec<Item>,
+    #[serde(default)]
+    pub projects: Vec<Project>,
+    #[serde(default)]
+    pub sections: Vec<Section>,
+    /// The ids of the items matching each filter in config, by name
+    #[serde(default)]
+    pub filters: HashMap<String, Vec<String>>,
+    /// Items completed from tot, most recent last
+    #[serde(default)]
+    pub closed: Vec<Item>,
+    /// The resource types the sync token was given for
+    #[serde(default)]
+    pub resource_types: Vec<String>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache {
+            sync_token: String::from(FULL_SYNC_TOKEN),
+            items: Vec::new(),
+            projects: Vec::new(),
+            sections: Vec::new(),
+            filters: HashMap::new(),
+            closed: Vec::new(),
+            resource_types: Vec::new(),
+        }
+    }
+}
+
+impl Cache {
+    /// True once there has been at least one successful sync
+    pub fn is_synced(&self) -> bool {
+        self.sync_token != FULL_SYNC_TOKEN
+    }
+
+    /// Merge a sync response, a full sync replaces everything we had
+    pub fn apply(self, response: SyncResponse) -> Cache {
+        let (mut items, mut projects, mut sections) = if response.full_sync {
+            (Vec::new(), Vec::new(), Vec::new())
+        } else {
+            (self.items, self.projects, self.sections)
+        };
+
+        for item in response.items {
+            items.retain(|cached| cached.id != item.id);
+            if !item.checked && !item.is_deleted {
+                items.push(item);
+            }
+        }
+
+        for project in response.projects {
+            projects.retain(|cached| cached.id != project.id);
+            if !project.is_archived && !project.is_deleted {
+                projects.push(project);
+            }
+        }
+
+        for section in response.sections {
+            sections.retain(|cached| cached.id != section.id);
+            if !section.is_archived && !section.is_deleted {
+                sections.push(section);
+            }
+        }
+
+        Cache {
+            sync_token: response.sync_token.unwrap_or(self.sync_token),
+            items,
+            projects,
+            sections,
+            filters: self.filters,
+            closed: self.closed,
+            resource_types: self.resource_types,
+        }
+    }
+
+    /// Items matched by the filter when it was last fetched, that are still open
+    pub fn items_for_filter(&self, name: &str) -> Vec<Item> {
+        let ids = self.filters.get(name).cloned().unwrap_or_default();
+        self.items
+            .iter()
+            .filter(|item| ids.contains(&item.id))
+            .cloned()
+            .collect()
+    }
+
+    /// The id of the Inbox, unknown until the projects have been synced
+    pub fn inbox_id(&self) -> Option<String> {
+        self.projects
+            .iter()
+            .find(|project| project.inbox_project)
+            .map(|project| project.id.clone())
+    }
+
+    pub fn items_for_project(&self, project_id: &str) -> Vec<Item> {
+        self.items
+            .iter()
+            .filter(|item| item.project_id == project_id)
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn path(config: &Config) -> PathBuf {
+    store::path(config, FILENAME)
+}
+
+pub fn load(config: &Config) -> Result<Cache, Error> {
+    store::load(&path(config))
+}
+
+fn save(config: &Config, cache: &Cache) -> Result<(), Error> {
+    store::save(&path(config), cache)
+}
+
+/// Fetch the changes since the last sync and save them
+pub fn refresh(config: &Config) -> Result<Cache, Error> {
+    // An unreadable cache is thrown away, the full sync that follows rebuilds it
+    let cache = load(config).unwrap_or_default();
+    // Resources added since the last sync would only be sent once they change
+    let sync_token = match cache.resource_types == request::RESOURCE_TYPES {
+        true => cache.sync_token.clone(),
+        false => String::from(FULL_SYNC_TOKEN),
+    };
+    let response = request::sync_resources(config, &sync_token)?;
+    let cache = Cache {
+        resource_types: request::RESOURCE_TYPES.map(String::from).to_vec(),
+        ..cache.apply(response)
+    };
+    save(config, &cache)?;
+
+    Ok(cache)
+}
+
+/// Drop an item straight away when it has been completed, keeping it aside in case of an undo
+pub fn remove_item(config: &Config, id: &str) -> Result<(), Error> {
+    let mut cache = load(config).unwrap_or_default();
+    let (removed, items) = cache.items.into_iter().partition(|item| item.id == id);
+    cache.items = items;
+    cache.closed.retain(|item| item.id != id);
+    cache.closed.extend::<Vec<Item>>(removed);
+    let excess = cache.closed.len().saturating_sub(CLOSED_LIMIT);
+    cache.closed.drain(..excess);
+    save(config, &cache)
+}
+
+/// Save the ids of the items matching a filter
+pub fn set_filter(config: &Config, name: &str, ids: Vec<String>) -> Result<(), Error> {
+    let mut cache = load(config).unwrap_or_default();
+    cache.filters.insert(name.to_owned(), ids);
+    save(config, &cache)
+}
+
+/// Put a completed item back, i.e. when its completion has been undone
+pub fn restore_item(config: &Config, id: &str) -> Result<(), Error> {
+    let mut cache = load(config).unwrap_or_default();
+    let (restored, closed) = cache.closed.into_iter().partition(|item| item.id == id);
+    cache.closed = closed;
+    cache.items.retain(|item| item.id != id);
+    cache.items.extend::<Vec<Item>>(restored);
+    save(config, &cache)
+}
+
+/// Replace an item straight away, i.e. when its due date has been changed
+pub fn update_item(config: &Config, item: Item) -> Result<(), Error> {
+    let mut cache = load(config).unwrap_or_default();
+    cache.items.retain(|cached| cached.id != item.id);
+    cache.items.push(item);
+    save(config, &cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub_server::{self, Reply, StubServer};
+    use crate::test;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn item(id: &str, content: &str) -> Item {
+        Item {
+            id: String::from(id),
+            content: String::from(content),
+            ..test::helpers::item_fixture()
+        }
+    }
+
+    fn project(id: &str, name: &str) -> Project {
+        Project {
+            id: String::from(id),
+            name: String::from(name),
+            is_archived: false,
+            is_deleted: false,
+            inbox_project: false,
+        }
+    }
+
+    #[test]
+    fn apply_merges_incremental_syncs() {
+        let cache = Cache {
+            sync_token: String::from("first"),
+            items: vec![item("1", "Keep"), item("2", "Change"), item("3", "Close")],
+            projects: vec![project("1", "Home"), project("2", "Work")],
+            sections: Vec::new(),
+            filters: HashMap::new(),
+            closed: Vec::new(),
+            resource_types: Vec::new(),
+        };
+        let response = SyncResponse {
+            sync_token: Some(String::from("second")),
+            items: vec![
+                item("2", "Changed"),
+                Item {
+                    checked: true,
+                    ..item("3", "Close")
+                },
+                item("4", "New"),
+            ],
+            projects: vec![
+                Project {
+                    is_archived: true,
+                    ..project("2", "Work")
+                },
+                project("3", "Errands"),
+            ],
+            ..SyncResponse::default()
+        };
+
+        let expected = Cache {
+            sync_token: String::from("second"),
+            items: vec![item("1", "Keep"), item("2", "Changed"), item("4", "New")],
+            projects: vec![project("1", "Home"), project("3", "Errands")],
+            sections: Vec::new(),
+            filters: HashMap::new(),
+            closed: Vec::new(),
+            resource_types: Vec::new(),
+        };
+        assert_eq!(cache.apply(response), expected);
+    }
+
+    #[test]
+    fn apply_replaces_everything_on_full_sync() {
+        let cache = Cache {
+            sync_token: String::from("first"),
+            items: vec![item("1", "Gone")],
+            projects: vec![project("1", "Gone")],
+            sections: Vec::new(),
+            filters: HashMap::new(),
+            closed: Vec::new(),
+            resource_types: Vec::new(),
+        };
+        let response = SyncResponse {
+            full_sync: true,
+            sync_token: Some(String::from("second")),
+            items: vec![item("2", "Fresh")],
+            ..SyncResponse::default()
+        };
+
+        let cache = cache.apply(response);
+        assert_eq!(cache.items, vec![item("2", "Fresh")]);
+        assert_eq!(cache.projects, Vec::new());
+    }
+
+    #[test]
+    fn refresh_sends_the_sync_token_and_survives_restarts() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+
+        let cache = refresh(&config).unwrap();
+        assert!(cache.is_synced());
+        assert_eq!(cache.items_for_project("2203306141").len(), 3);
+
+        let body = json!({
+            "full_sync": false,
+            "sync_token": "next",
+            "items": [{
+                "id": "6543210001",
+                "project_id": "2203306141",
+                "content": "Water the plants",
+                "description": "",
+                "priority": 1,
+                "checked": true,
+                "is_deleted": false,
+                "due": null
+            }]
+        });
+        server.script(vec![Reply {
+            body: body.to_string(),
+            ..Reply::status(200)
+        }]);
+
+        let cache = refresh(&config).unwrap();
+        assert_eq!(load(&config), Ok(cache.clone()));
+        assert_eq!(cache.sync_token, String::from("next"));
+        assert_eq!(cache.items_for_project("2203306141").len(), 2);
+
+        let requests = server.requests();
+        assert!(requests[0].body.contains("\"sync_token\":\"*\""));
+        assert!(requests[1].body.contains(
+            "\"sync_token\":\"TnYUZEpuzf2FMA9qzyY3j4xky6dXiYejmSO85S5paZ_a9y1FI85mBbIWZGpW\""
+        ));
+    }
+
+    #[test]
+    fn removed_items_can_be_restored() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let cache = Cache {
+            items: vec![item("1", "Keep"), item("2", "Close")],
+            ..Cache::default()
+        };
+        save(&config, &cache).unwrap();
+
+        remove_item(&config, "2").unwrap();
+        let cache = load(&config).unwrap();
+        assert_eq!(cache.items, vec![item("1", "Keep")]);
+        assert_eq!(cache.closed, vec![item("2", "Close")]);
+
+        restore_item(&config, "2").unwrap();
+        let cache = load(&config).unwrap();
+        assert_eq!(cache.items, vec![item("1", "Keep"), item("2", "Close")]);
+        assert_eq!(cache.closed, Vec::new());
+    }
+
+    #[test]
+    fn refresh_starts_over_when_the_cache_is_unreadable() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        std::fs::write(path(&config), "{ not json").unwrap();
+
+        assert_eq!(refresh(&config).unwrap().items.len(), 4);
+        assert!(server.requests()[0].body.contains("\"sync_token\":\"*\""));
+    }
+
+    #[test]
+    fn refresh_starts_over_when_the_resource_types_change() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let cache = Cache {
+            sync_token: String::from("items only"),
+            items: vec![item("1", "Old")],
+            resource_types: vec![String::from("items")],
+            ..Cache::default()
+        };
+        save(&config, &cache).unwrap();
+
+        let cache = refresh(&config).unwrap();
+        assert!(server.requests()[0].body.contains("\"sync_token\":\"*\""));
+        assert_eq!(cache.items.len(), 4);
+
+        refresh(&config).unwrap();
+        assert!(!server.requests()[1].body.contains("\"sync_token\":\"*\""));
+    }
+}
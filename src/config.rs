@@ -1,5 +1,8 @@
+use crate::backend::Backend;
+use crate::error::Error;
+use crate::ranking::Ranking;
 use crate::time;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
 use std::io::{Read, Write};
@@ -10,63 +13,245 @@ use std::{fs, io};
 pub struct Config {
     /// The Todoist Api token
     pub token: String,
-    /// List of Todoist projects and their project numbers
-    pub projects: HashMap<String, u32>,
+    /// List of Todoist projects and their project ids
+    #[serde(deserialize_with = "deserialize_project_ids")]
+    pub projects: HashMap<String, String>,
     /// Path to config file
     pub path: String,
     /// The ID of the next task
     pub next_id: Option<String>,
     pub timezone: Option<String>,
     pub last_version_check: Option<String>,
+    /// Base URL of the Todoist API, defaults to https://api.todoist.com
+    pub todoist_url: Option<String>,
+    /// Timeouts and retries for requests to Todoist
+    #[serde(default)]
+    pub network: Network,
+    /// Names of the projects shown in the bottom bar, all projects when None
+    pub visible_projects: Option<Vec<String>>,
+    /// How to rank the items in each project by name, the default ranking when missing
+    #[serde(default)]
+    pub ranking: HashMap<String, Ranking>,
+    /// Weights for the default ranking
+    #[serde(default)]
+    pub scoring: Scoring,
+    /// How many of the next items to list beneath the current one, none when 0
+    #[serde(default)]
+    pub up_next: usize,
+    /// Items with any of these labels are never shown
+    #[serde(default)]
+    pub exclude_labels: Vec<String>,
+    /// Which subtasks of a parent can be shown, the parent is shown once they are done
+    #[serde(default)]
+    pub subtasks: Subtasks,
+    /// Views shown alongside projects, by name, of the items matching a Todoist filter query
+    #[serde(default)]
+    pub filters: HashMap<String, String>,
+    /// Where items come from, Todoist by default
+    #[serde(default)]
+    pub backend: Backend,
+    /// Projects hidden in the GUI until it is restarted, left out of "All projects", not saved
+    #[serde(skip)]
+    pub hidden_projects: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Debug)]
+#[serde(default)]
+pub struct Network {
+    /// Seconds to wait for a connection
+    pub connect_timeout: u64,
+    /// Seconds to wait for a whole request
+    pub timeout: u64,
+    /// How many times to retry after the first attempt fails
+    pub max_retries: u32,
+    /// Milliseconds to wait before the first retry, doubled for each retry after
+    pub retry_delay: u64,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Network {
+            connect_timeout: 10,
+            timeout: 30,
+            max_retries: 3,
+            retry_delay: 500,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subtasks {
+    /// Any open subtask
+    #[default]
+    All,
+    /// Only the first open subtask, so they are done in order
+    First,
+}
+
+/// Largest weight allowed, so that adding weights together can't overflow
+const MAX_WEIGHT: u32 = 1_000_000;
+
+/// Largest time window allowed, in minutes either side of the due time
+const MAX_TIME_WINDOW: i64 = 12 * 60;
+
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Debug)]
+#[serde(default)]
+pub struct Scoring {
+    /// Items without a due date
+    pub no_date: u32,
+    /// Items due today
+    pub today: u32,
+    /// Items due before today
+    pub overdue: u32,
+    /// Items that don't repeat, so are not done again soon after
+    pub not_recurring: u32,
+    /// Items with a due time close to now
+    pub time_window: u32,
+    /// How many minutes either side of the due time count as close
+    pub time_window_minutes: i64,
+    /// Todoist priorities 1 (lowest) to 4 (highest)
+    pub priority: [u32; 4],
+    /// Added for each label an item has, negative to push items back, i.e. {"waiting": -100}
+    pub labels: HashMap<String, i64>,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Scoring {
+            no_date: 80,
+            today: 100,
+            overdue: 150,
+            not_recurring: 50,
+            time_window: 200,
+            time_window_minutes: 15,
+            priority: [2, 1, 3, 4],
+            labels: HashMap::new(),
+        }
+    }
+}
+
+impl Scoring {
+    fn validate(&self) -> Result<(), String> {
+        let weights = [
+            ("no_date", self.no_date),
+            ("today", self.today),
+            ("overdue", self.overdue),
+            ("not_recurring", self.not_recurring),
+            ("time_window", self.time_window),
+        ];
+        let priorities = self.priority.iter().map(|weight| ("priority", *weight));
+
+        for (name, weight) in weights.into_iter().chain(priorities) {
+            if weight > MAX_WEIGHT {
+                return Err(format!("scoring.{name} must be at most {MAX_WEIGHT}"));
+            }
+        }
+        for (label, weight) in &self.labels {
+            if weight.unsigned_abs() > MAX_WEIGHT as u64 {
+                return Err(format!(
+                    "scoring.labels.{label} must be between -{MAX_WEIGHT} and {MAX_WEIGHT}"
+                ));
+            }
+        }
+        if !(0..=MAX_TIME_WINDOW).contains(&self.time_window_minutes) {
+            return Err(format!(
+                "scoring.time_window_minutes must be between 0 and {MAX_TIME_WINDOW}"
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 impl Config {
-    pub fn new(token: &str) -> Result<Config, String> {
-        let projects: HashMap<String, u32> = HashMap::new();
+    pub fn new(token: &str) -> Result<Config, Error> {
+        let projects: HashMap<String, String> = HashMap::new();
         Ok(Config {
             path: generate_path()?,
             token: String::from(token),
             next_id: None,
             last_version_check: None,
             timezone: None,
+            todoist_url: None,
+            network: Network::default(),
+            visible_projects: None,
+            ranking: HashMap::new(),
+            scoring: Scoring::default(),
+            up_next: 0,
+            exclude_labels: Vec::new(),
+            subtasks: Subtasks::default(),
+            filters: HashMap::new(),
+            backend: Backend::default(),
+            hidden_projects: Vec::new(),
             projects,
         })
     }
 
-    pub fn create(self) -> Result<Config, String> {
+    pub fn create(self) -> Result<Config, Error> {
         let json = json!(self).to_string();
-        let mut file = fs::File::create(&self.path).or(Err("Could not create file"))?;
+        let mut file = fs::File::create(&self.path).map_err(|e| Error::config_io(&self.path, e))?;
         file.write_all(json.as_bytes())
-            .or(Err("Could not write to file"))?;
+            .map_err(|e| Error::config_io(&self.path, e))?;
         println!("Config successfully created in {}", &self.path);
         Ok(self)
     }
 
-    pub fn save(self) -> std::result::Result<String, String> {
+    pub fn save(self) -> std::result::Result<String, Error> {
         let json = json!(self);
-        let string = serde_json::to_string_pretty(&json).or(Err("Could not convert to JSON"))?;
+        let string = serde_json::to_string_pretty(&json).map_err(|e| Error::ConfigParse {
+            path: self.path.clone(),
+            cause: e.to_string(),
+        })?;
 
         fs::OpenOptions::new()
             .write(true)
             .read(true)
             .truncate(true)
             .open(&self.path)
-            .or(Err("Could not find config"))?
+            .map_err(|e| Error::config_io(&self.path, e))?
             .write_all(string.as_bytes())
-            .or(Err("Could not write to file"))?;
+            .map_err(|e| Error::config_io(&self.path, e))?;
 
         Ok(String::from("✓"))
     }
 
-    pub fn load(path: &str) -> Result<Config, String> {
+    pub fn load(path: &str) -> Result<Config, Error> {
         let mut json = String::new();
 
         fs::File::open(path)
-            .or(Err("Could not find file"))?
+            .map_err(|e| Error::config_io(path, e))?
             .read_to_string(&mut json)
-            .or(Err("Could not read to string"))?;
+            .map_err(|e| Error::config_io(path, e))?;
+
+        let config = serde_json::from_str::<Config>(&json).map_err(|e| Error::ConfigParse {
+            path: path.to_owned(),
+            cause: e.to_string(),
+        })?;
 
-        serde_json::from_str::<Config>(&json).map_err(|_| String::from("Could not parse JSON"))
+        time::timezone_from_str(&config.timezone).map_err(|e| Error::ConfigParse {
+            path: path.to_owned(),
+            cause: e.to_string(),
+        })?;
+
+        config
+            .scoring
+            .validate()
+            .map_err(|cause| Error::ConfigParse {
+                path: path.to_owned(),
+                cause,
+            })?;
+
+        if has_numeric_project_ids(&json) {
+            // Rewrite the file in place so the old numeric ids are only migrated once
+            let string = serde_json::to_string_pretty(&config).map_err(|e| Error::ConfigParse {
+                path: path.to_owned(),
+                cause: e.to_string(),
+            })?;
+            fs::write(path, string).map_err(|e| Error::config_io(path, e))?;
+        }
+
+        Ok(config)
     }
 
     pub fn set_path(self, path: &str) -> Config {
@@ -85,19 +270,26 @@ impl Config {
         }
     }
 
+    pub fn set_visible_projects(&self, names: Vec<String>) -> Config {
+        Config {
+            visible_projects: Some(names),
+            ..self.clone()
+        }
+    }
+
     pub fn clear_next_id(self) -> Config {
         let next_id: Option<String> = None;
 
         Config { next_id, ..self }
     }
 
-    fn check_for_timezone(self: Config) -> Result<Config, String> {
+    fn check_for_timezone(self: Config) -> Result<Config, Error> {
         if self.timezone.is_none() {
             time::list_timezones();
             let desc = "Please enter the number of your timezone";
             let num: usize = get_input(desc)?
                 .parse::<usize>()
-                .map_err(|_| String::from("Could not parse string into number"))?;
+                .map_err(|e| Error::Input(e.to_string()))?;
             let config = Config {
                 timezone: Some(time::get_timezone(num)),
                 ..self
@@ -112,7 +304,7 @@ impl Config {
     }
 }
 
-pub fn get_or_create(config_path: Option<&str>) -> Result<Config, String> {
+pub fn get_or_create(config_path: Option<&str>) -> Result<Config, Error> {
     let path: String = match config_path {
         None => generate_path()?,
         Some(path) => String::from(path).trim().to_owned(),
@@ -125,7 +317,7 @@ pub fn get_or_create(config_path: Option<&str>) -> Result<Config, String> {
         let legacy_path = generate_legacy_path()?;
         if path_exists(&legacy_path) {
             println!("INFO: Moving the config file from \"{legacy_path}\" to \"{path}\".\n");
-            fs::rename(legacy_path, &path).map_err(|e| e.to_string())?;
+            fs::rename(legacy_path, &path).map_err(|e| Error::config_io(&path, e))?;
         }
     }
 
@@ -149,33 +341,63 @@ pub fn get_or_create(config_path: Option<&str>) -> Result<Config, String> {
     }
 }
 
+/// Project ids used to be saved as numbers, Todoist ids are strings that can exceed 32 bits
+fn deserialize_project_ids<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Number(u64),
+        String(String),
+    }
+
+    let ids = HashMap::<String, Id>::deserialize(deserializer)?;
+    Ok(ids
+        .into_iter()
+        .map(|(name, id)| match id {
+            Id::Number(number) => (name, number.to_string()),
+            Id::String(string) => (name, string),
+        })
+        .collect())
+}
+
+fn has_numeric_project_ids(json: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(json)
+        .ok()
+        .and_then(|value| value["projects"].as_object().cloned())
+        .map(|projects| projects.values().any(|id| id.is_number()))
+        .unwrap_or(false)
+}
+
 fn path_exists(path: &str) -> bool {
     std::path::Path::new(path).exists()
 }
 
-pub fn generate_path() -> Result<String, String> {
+pub fn generate_path() -> Result<String, Error> {
     let filename = if cfg!(test) { "test" } else { "tod.cfg" };
 
     let config_directory = dirs::config_dir()
-        .ok_or_else(|| String::from("Could not find config directory"))?
+        .ok_or_else(|| Error::config_io(filename, "Could not find config directory"))?
         .to_str()
-        .ok_or_else(|| String::from("Could not convert config directory to string"))?
+        .ok_or_else(|| Error::config_io(filename, "Could not convert config directory to string"))?
         .to_owned();
     Ok(format!("{config_directory}/{filename}"))
 }
 
-pub fn generate_legacy_path() -> Result<String, String> {
+pub fn generate_legacy_path() -> Result<String, Error> {
     let filename = if cfg!(test) { "test" } else { ".tod.cfg" };
 
     let home_directory = dirs::home_dir()
-        .ok_or_else(|| String::from("Could not find home directory"))?
+        .ok_or_else(|| Error::config_io(filename, "Could not find home directory"))?
         .to_str()
-        .ok_or_else(|| String::from("Could not convert directory to string"))?
+        .ok_or_else(|| Error::config_io(filename, "Could not convert directory to string"))?
         .to_owned();
     Ok(format!("{home_directory}/{filename}"))
 }
 
-pub fn get_input(desc: &str) -> Result<String, String> {
+pub fn get_input(desc: &str) -> Result<String, Error> {
     if cfg!(test) {
         return Ok(String::from("5"));
     }
@@ -184,7 +406,65 @@ pub fn get_input(desc: &str) -> Result<String, String> {
     println!("{desc}");
     io::stdin()
         .read_line(&mut input)
-        .or(Err("error: unable to read user input"))?;
+        .map_err(|e| Error::Input(e.to_string()))?;
 
     Ok(String::from(input.trim()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn load_migrates_numeric_project_ids() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&config.path).unwrap()).unwrap();
+        json["projects"] = json!({"Home": 2203306141_u64, "Work": "6Jf8VQXxpwv56VQ7"});
+        fs::write(&config.path, json.to_string()).unwrap();
+
+        let loaded = Config::load(&config.path).unwrap();
+
+        let expected = HashMap::from([
+            (String::from("Home"), String::from("2203306141")),
+            (String::from("Work"), String::from("6Jf8VQXxpwv56VQ7")),
+        ]);
+        assert_eq!(loaded.projects, expected);
+
+        let rewritten = fs::read_to_string(&config.path).unwrap();
+        assert!(!has_numeric_project_ids(&rewritten));
+        assert_eq!(Config::load(&config.path).unwrap(), loaded);
+    }
+
+    #[test]
+    fn load_defaults_and_validates_scoring() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&config.path).unwrap()).unwrap();
+
+        json.as_object_mut().unwrap().remove("scoring");
+        fs::write(&config.path, json.to_string()).unwrap();
+        assert_eq!(
+            Config::load(&config.path).unwrap().scoring,
+            Scoring::default()
+        );
+
+        json["scoring"] = json!({"overdue": 300});
+        fs::write(&config.path, json.to_string()).unwrap();
+        let scoring = Config::load(&config.path).unwrap().scoring;
+        assert_eq!(scoring.overdue, 300);
+        assert_eq!(scoring.today, 100);
+
+        json["scoring"] = json!({"time_window_minutes": -5});
+        fs::write(&config.path, json.to_string()).unwrap();
+        assert_eq!(
+            Config::load(&config.path),
+            Err(Error::ConfigParse {
+                path: config.path.clone(),
+                cause: String::from("scoring.time_window_minutes must be between 0 and 720")
+            })
+        );
+    }
+}
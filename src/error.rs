@@ -0,0 +1,157 @@
+use std::fmt;
+
+/// Everything that can go wrong, with enough detail to tell the user what to do about it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Could not reach Todoist, or the connection dropped
+    Network(String),
+    /// Todoist rejected the API token (401 or 403)
+    Auth { status: u16 },
+    /// Too many requests (429), retry_after is in seconds
+    RateLimit { retry_after: Option<u64> },
+    /// Todoist had a problem (5xx)
+    Server { status: u16, body: String },
+    /// Any other unsuccessful response
+    Response { status: u16, body: String },
+    /// The response did not have the shape we expected
+    ResponseParse { what: String, cause: String },
+    /// The config file could not be found, read or written
+    ConfigIo { path: String, cause: String },
+    /// The config file is not valid JSON for a Config
+    ConfigParse { path: String, cause: String },
+    /// A date, datetime or timezone could not be parsed
+    DateParse { input: String, cause: String },
+    /// Todoist refused a sync command, i.e. the item no longer exists
+    CommandRejected { command: String, cause: String },
+    /// The project name is neither in config nor in the Todoist account
+    ProjectNotFound(String),
+    /// No item has the id, i.e. it was removed from the file since it was shown
+    ItemNotFound(String),
+    /// Could not read from stdin
+    Input(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Network(cause) => write!(
+                f,
+                "Could not connect to Todoist, please check your internet connection: {cause}"
+            ),
+            Error::Auth { status } => write!(
+                f,
+                "Todoist rejected the API token ({status}), please check the token in your config"
+            ),
+            Error::RateLimit {
+                retry_after: Some(seconds),
+            } => write!(
+                f,
+                "Todoist is limiting requests, please try again in {seconds} seconds"
+            ),
+            Error::RateLimit { retry_after: None } => {
+                write!(f, "Todoist is limiting requests, please try again shortly")
+            }
+            Error::Server { status, body } => {
+                write!(f, "Todoist had a server error ({status}): {body}")
+            }
+            Error::Response { status, body } => {
+                write!(f, "Unexpected response from Todoist ({status}): {body}")
+            }
+            Error::ResponseParse { what, cause } => {
+                write!(f, "Could not parse response for {what}: {cause}")
+            }
+            Error::ConfigIo { path, cause } => write!(f, "Could not access {path}: {cause}"),
+            Error::ConfigParse { path, cause } => write!(f, "Could not parse {path}: {cause}"),
+            Error::DateParse { input, cause } => write!(f, "Could not parse {input}: {cause}"),
+            Error::CommandRejected { command, cause } => {
+                write!(f, "Todoist rejected {command}: {cause}")
+            }
+            Error::ProjectNotFound(name) => {
+                write!(
+                    f,
+                    "Project {name} not found in the Todoist account, it may have been deleted"
+                )
+            }
+            Error::ItemNotFound(id) => {
+                write!(f, "Task {id} not found, it may have been changed elsewhere")
+            }
+            Error::Input(cause) => write!(f, "Unable to read user input: {cause}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        Error::Network(error.to_string())
+    }
+}
+
+impl Error {
+    /// Classify an unsuccessful HTTP response by its status code
+    pub fn from_status(status: u16, retry_after: Option<u64>, body: String) -> Error {
+        match status {
+            401 | 403 => Error::Auth { status },
+            429 => Error::RateLimit { retry_after },
+            500..=599 => Error::Server { status, body },
+            _ => Error::Response { status, body },
+        }
+    }
+
+    /// True when the request may succeed later without anything being changed
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Error::Network(_) | Error::Server { .. } | Error::RateLimit { .. }
+        )
+    }
+
+    pub fn config_io(path: &str, cause: impl ToString) -> Error {
+        Error::ConfigIo {
+            path: path.to_owned(),
+            cause: cause.to_string(),
+        }
+    }
+
+    pub fn date_parse(input: &str, cause: impl ToString) -> Error {
+        Error::DateParse {
+            input: input.to_owned(),
+            cause: cause.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn from_status_classifies_responses() {
+        assert_eq!(
+            Error::from_status(401, None, String::new()),
+            Error::Auth { status: 401 }
+        );
+        assert_eq!(
+            Error::from_status(429, Some(30), String::new()),
+            Error::RateLimit {
+                retry_after: Some(30)
+            }
+        );
+        assert_eq!(
+            Error::from_status(502, None, String::from("Bad Gateway")),
+            Error::Server {
+                status: 502,
+                body: String::from("Bad Gateway")
+            }
+        );
+        assert_eq!(
+            Error::from_status(400, None, String::from("Bad Request")),
+            Error::Response {
+                status: 400,
+                body: String::from("Bad Request")
+            }
+        );
+    }
+}
@@ -0,0 +1,852 @@
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, Utc, Weekday};
+use chrono_tz::Tz;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use crate::backend::TaskBackend;
+use crate::config::Config;
+use crate::error::Error;
+use crate::items::{DateInfo, Item};
+use crate::projects::ALL_PROJECTS;
+use crate::store;
+
+/// VTODOs in .ics files, as vdirsyncer keeps them for CalDAV servers like Nextcloud.
+/// The directory and each directory in it with .ics files are projects named after them,
+/// ids are UIDs.
+pub struct Ics {
+    pub directory: String,
+}
+
+impl TaskBackend for Ics {
+    /// Syncing with the CalDAV server is left to vdirsyncer
+    fn refresh(&self, _config: &Config, _view: Option<&str>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn views(&self, _config: &Config) -> Result<Vec<String>, Error> {
+        let mut names: Vec<String> = Vec::new();
+        for path in self.files()? {
+            let name = project_name(&path);
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        names.sort();
+        Ok(names)
+    }
+
+    /// Every open item for "All projects", apart from those of hidden calendars
+    fn items(&self, config: &Config, view: &str) -> Result<Vec<Item>, Error> {
+        let in_view = |item: &Item| match view {
+            ALL_PROJECTS => !config.hidden_projects.contains(&item.project_id),
+            _ => item.project_id == view,
+        };
+        Ok(self
+            .read_items()?
+            .into_iter()
+            .filter(|item| !item.checked && in_view(item))
+            .collect())
+    }
+
+    /// The collections are watched as well, vdirsyncer adds a file to one for each new task
+    fn modified(&self, _config: &Config) -> Option<SystemTime> {
+        let mut paths = self.files().ok()?;
+        paths.extend(self.collections().ok()?);
+        store::modified(&paths)
+    }
+
+    /// An item with an RRULE moves on to its next occurrence instead, completing it would
+    /// complete the whole series. It is completed when the series is over, or when the rule
+    /// can't be followed.
+    fn complete(&self, _config: &Config, id: &str) -> Result<(), Error> {
+        let now = timestamp();
+        self.update_todo(id, |todo| {
+            let rule = get_property(todo, "RRULE");
+            match rule.and_then(|rule| move_series(todo, &rule, true)) {
+                Some(moved) => *todo = moved,
+                None => {
+                    set_property(todo, "STATUS", Some(String::from("STATUS:COMPLETED")));
+                    set_property(todo, "COMPLETED", Some(format!("COMPLETED:{now}")));
+                    let percent = String::from("PERCENT-COMPLETE:100");
+                    set_property(todo, "PERCENT-COMPLETE", Some(percent));
+                }
+            }
+            set_property(todo, "LAST-MODIFIED", Some(format!("LAST-MODIFIED:{now}")));
+        })
+    }
+
+    /// An open item with an RRULE moves back to the occurrence before
+    fn uncomplete(&self, _config: &Config, id: &str) -> Result<(), Error> {
+        let now = timestamp();
+        self.update_todo(id, |todo| {
+            let is_completed = get_property(todo, "COMPLETED").is_some()
+                || get_property(todo, "STATUS").is_some_and(|status| status == "COMPLETED");
+            let rule = get_property(todo, "RRULE").filter(|_| !is_completed);
+            match rule.and_then(|rule| move_series(todo, &rule, false)) {
+                Some(moved) => *todo = moved,
+                None => {
+                    set_property(todo, "STATUS", Some(String::from("STATUS:NEEDS-ACTION")));
+                    set_property(todo, "COMPLETED", None);
+                    set_property(todo, "PERCENT-COMPLETE", None);
+                }
+            }
+            set_property(todo, "LAST-MODIFIED", Some(format!("LAST-MODIFIED:{now}")));
+        })
+    }
+
+    /// Dates are written as dates and datetimes in floating time, as the GUI picks them
+    fn update_due(&self, _config: &Config, id: &str, date: &str) -> Result<(), Error> {
+        let due = match date.len() {
+            10 => format!("DUE;VALUE=DATE:{}", date.replace('-', "")),
+            _ => format!("DUE:{}", date.replace(['-', ':'], "")),
+        };
+        let now = timestamp();
+        self.update_todo(id, |todo| {
+            set_property(todo, "DUE", Some(due));
+            set_property(todo, "LAST-MODIFIED", Some(format!("LAST-MODIFIED:{now}")));
+        })
+    }
+
+    /// A new file in the view's directory, or the directory itself for "All projects"
+    fn add(&self, _config: &Config, view: &str, text: &str) -> Result<(), Error> {
+        let directory = if view == ALL_PROJECTS || view == directory_name(&self.path()) {
+            self.path()
+        } else {
+            self.path().join(view)
+        };
+        fs::create_dir_all(&directory)
+            .map_err(|e| Error::config_io(&directory.display().to_string(), e))?;
+
+        let uid = Uuid::new_v4();
+        let now = timestamp();
+        let lines = vec![
+            String::from("BEGIN:VCALENDAR"),
+            String::from("VERSION:2.0"),
+            String::from("PRODID:-//tot//EN"),
+            String::from("BEGIN:VTODO"),
+            format!("UID:{uid}"),
+            format!("DTSTAMP:{now}"),
+            format!("CREATED:{now}"),
+            format!("SUMMARY:{}", escape(text.trim())),
+            String::from("STATUS:NEEDS-ACTION"),
+            String::from("END:VTODO"),
+            String::from("END:VCALENDAR"),
+        ];
+        write_lines(&directory.join(format!("{uid}.ics")), lines)
+    }
+
+    /// Adds the parent named by RELATED-TO, and the collection in "All projects"
+    fn fmt_item(&self, config: &Config, item: &Item, view: &str) -> Result<String, Error> {
+        let parent = match &item.parent_id {
+            Some(parent_id) => self
+                .read_items()?
+                .into_iter()
+                .find(|parent| &parent.id == parent_id)
+                .map(|parent| parent.content),
+            None => None,
+        };
+
+        let text = item.fmt(config, parent.as_deref(), None);
+        match view {
+            ALL_PROJECTS => Ok(format!("{text}\nProject: {}", item.project_id)),
+            _ => Ok(text),
+        }
+    }
+}
+
+impl Ics {
+    fn path(&self) -> PathBuf {
+        PathBuf::from(&self.directory)
+    }
+
+    /// The directory and the directories in it, vdirsyncer keeps a calendar in each
+    fn collections(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut collections: Vec<PathBuf> = store::read_dir(&self.path())?
+            .into_iter()
+            .filter(|path| path.is_dir())
+            .collect();
+        collections.insert(0, self.path());
+        Ok(collections)
+    }
+
+    /// The .ics files in the collections, one for each VTODO or event
+    fn files(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut files = Vec::new();
+        for collection in self.collections()? {
+            files.extend(
+                store::read_dir(&collection)?
+                    .into_iter()
+                    .filter(|path| path.extension().is_some_and(|extension| extension == "ics")),
+            );
+        }
+
+        Ok(files)
+    }
+
+    fn read_items(&self) -> Result<Vec<Item>, Error> {
+        let mut items = Vec::new();
+        for path in self.files()? {
+            items.extend(parse_lines(&project_name(&path), &read_lines(&path)?));
+        }
+
+        Ok(items)
+    }
+
+    /// Change the lines of the VTODO with the UID, from BEGIN:VTODO to END:VTODO
+    fn update_todo(&self, id: &str, update: impl FnOnce(&mut Vec<String>)) -> Result<(), Error> {
+        for path in self.files()? {
+            let mut lines = read_lines(&path)?;
+            if let Some((start, end)) = todo_range(&lines, id) {
+                let mut todo = lines[start..=end].to_vec();
+                update(&mut todo);
+                lines.splice(start..=end, todo);
+                return write_lines(&path, lines);
+            }
+        }
+
+        Err(Error::ItemNotFound(id.to_owned()))
+    }
+}
+
+/// The name of the directory an .ics file is in
+fn project_name(path: &Path) -> String {
+    path.parent().map(directory_name).unwrap_or_default()
+}
+
+fn directory_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn read_lines(path: &Path) -> Result<Vec<String>, Error> {
+    let text =
+        fs::read_to_string(path).map_err(|e| Error::config_io(&path.display().to_string(), e))?;
+    Ok(unfold(&text))
+}
+
+/// Lines with folded lines joined back up
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(previous)) => previous.push_str(rest),
+            _ => lines.push(line.to_owned()),
+        }
+    }
+
+    lines
+}
+
+/// Lines folded at 75 octets and ended with CRLF, as RFC 5545 asks
+fn write_lines(path: &Path, lines: Vec<String>) -> Result<(), Error> {
+    let mut text = String::new();
+    for line in lines {
+        let mut width = 0;
+        for character in line.chars() {
+            if width + character.len_utf8() > 75 {
+                text.push_str("\r\n ");
+                width = 1;
+            }
+            text.push(character);
+            width += character.len_utf8();
+        }
+        text.push_str("\r\n");
+    }
+
+    fs::write(path, text).map_err(|e| Error::config_io(&path.display().to_string(), e))
+}
+
+/// The VTODOs in the lines of a file.
+/// PRIORITY 1 to 4 is Todoist's p1, 5 is p2 and 6 to 9 is p3, completed and cancelled
+/// items are checked.
+pub fn parse_lines(project: &str, lines: &[String]) -> Vec<Item> {
+    let mut items = Vec::new();
+    let mut todo: Option<Item> = None;
+    // VALARMs in a VTODO have properties of their own
+    let mut depth = 0;
+
+    for line in lines {
+        let (name, params, value) = property(line);
+        match (name, value) {
+            ("BEGIN", "VTODO") => {
+                depth = 0;
+                todo = Some(Item {
+                    id: String::new(),
+                    project_id: project.to_owned(),
+                    content: String::new(),
+                    priority: 1,
+                    checked: false,
+                    description: String::new(),
+                    due: None,
+                    is_deleted: false,
+                    added_at: None,
+                    labels: Vec::new(),
+                    parent_id: None,
+                    section_id: None,
+                    child_order: 0,
+                });
+            }
+            ("END", "VTODO") => items.extend(todo.take()),
+            ("BEGIN", _) => depth += 1,
+            ("END", _) => depth -= 1,
+            _ => (),
+        }
+        let Some(item) = todo.as_mut().filter(|_| depth == 0) else {
+            continue;
+        };
+
+        match name {
+            "UID" => item.id = value.to_owned(),
+            "SUMMARY" => item.content = unescape(value),
+            "DESCRIPTION" => item.description = unescape(value),
+            "PRIORITY" => {
+                item.priority = match value.parse::<u8>() {
+                    Ok(1..=4) => 4,
+                    Ok(5) => 3,
+                    Ok(6..=9) => 2,
+                    _ => 1,
+                }
+            }
+            "STATUS" => item.checked = matches!(value, "COMPLETED" | "CANCELLED"),
+            "COMPLETED" => item.checked = true,
+            "DUE" => {
+                let is_recurring = item.due.as_ref().is_some_and(|due| due.is_recurring);
+                let string = item.due.take().map(|due| due.string).unwrap_or_default();
+                let timezone = parameter(params, "TZID").filter(|tz| tz.parse::<Tz>().is_ok());
+                item.due = iso_date(value).map(|date| DateInfo {
+                    date,
+                    is_recurring,
+                    timezone: timezone.map(String::from),
+                    string,
+                });
+            }
+            "RRULE" => match item.due.as_mut() {
+                Some(due) => {
+                    due.is_recurring = true;
+                    due.string = value.to_owned();
+                }
+                // Kept until DUE turns up
+                None => {
+                    item.due = Some(DateInfo {
+                        date: String::new(),
+                        is_recurring: true,
+                        timezone: None,
+                        string: value.to_owned(),
+                    })
+                }
+            },
+            "CREATED" => item.added_at = iso_date(value),
+            "CATEGORIES" => item
+                .labels
+                .extend(value.split(',').map(|label| unescape(label.trim()))),
+            "RELATED-TO" if parameter(params, "RELTYPE").unwrap_or("PARENT") == "PARENT" => {
+                item.parent_id = Some(value.to_owned())
+            }
+            _ => (),
+        }
+    }
+
+    // An RRULE without a DUE has no date to go on
+    for item in items.iter_mut() {
+        if item.due.as_ref().is_some_and(|due| due.date.is_empty()) {
+            item.due = None;
+        }
+    }
+    items
+}
+
+/// The name, parameters and value of a content line, i.e. "DUE;TZID=Europe/Paris:20230131T090000"
+fn property(line: &str) -> (&str, &str, &str) {
+    let mut quoted = false;
+    let colon = line.char_indices().find(|(_, character)| match character {
+        '"' => {
+            quoted = !quoted;
+            false
+        }
+        ':' => !quoted,
+        _ => false,
+    });
+    let (head, value) = match colon {
+        Some((index, _)) => (&line[..index], &line[index + 1..]),
+        None => (line, ""),
+    };
+    match head.split_once(';') {
+        Some((name, params)) => (name, params, value),
+        None => (head, "", value),
+    }
+}
+
+fn parameter<'a>(params: &'a str, name: &str) -> Option<&'a str> {
+    params
+        .split(';')
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim_matches('"'))
+}
+
+/// "20230131" as "2023-01-31" and "20230131T090000Z" as "2023-01-31T09:00:00Z"
+fn iso_date(value: &str) -> Option<String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Some(date.format("%Y-%m-%d").to_string());
+    }
+    let (value, zone) = match value.strip_suffix('Z') {
+        Some(value) => (value, "Z"),
+        None => (value, ""),
+    };
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|datetime| format!("{}{zone}", datetime.format("%Y-%m-%dT%H:%M:%S")))
+}
+
+/// Now in UTC, as DTSTAMP and COMPLETED are written
+fn timestamp() -> String {
+    Utc::now().format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        match (character, characters.clone().next()) {
+            ('\\', Some('n' | 'N')) => {
+                characters.next();
+                unescaped.push('\n');
+            }
+            ('\\', Some(escaped)) => {
+                characters.next();
+                unescaped.push(escaped);
+            }
+            _ => unescaped.push(character),
+        }
+    }
+
+    unescaped
+}
+
+/// Where the VTODO with the UID starts and ends
+fn todo_range(lines: &[String], id: &str) -> Option<(usize, usize)> {
+    let mut start = None;
+    for (index, line) in lines.iter().enumerate() {
+        match property(line) {
+            ("BEGIN", _, "VTODO") => start = Some(index),
+            ("END", _, "VTODO") => start = None,
+            ("UID", _, uid) if uid == id => {
+                let end = lines[index..]
+                    .iter()
+                    .position(|line| property(line) == ("END", "", "VTODO"))?;
+                return start.map(|start| (start, index + end));
+            }
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// The value of a property of a VTODO, not of the VALARMs within it
+fn get_property(todo: &[String], name: &str) -> Option<String> {
+    get_line(todo, name).map(|line| property(&line).2.to_owned())
+}
+
+/// The line of a property of a VTODO, with its parameters
+fn get_line(todo: &[String], name: &str) -> Option<String> {
+    let mut depth = 0;
+    for line in todo {
+        match property(line) {
+            ("BEGIN", _, "VTODO") | ("END", _, "VTODO") => (),
+            ("BEGIN", _, _) => depth += 1,
+            ("END", _, _) => depth -= 1,
+            (existing_name, _, _) if depth == 0 && existing_name == name => {
+                return Some(line.to_owned())
+            }
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// The parts of an RRULE that are followed, see RFC 5545 section 3.3.10
+struct Rule {
+    frequency: String,
+    interval: u32,
+    /// The weekdays of a WEEKLY rule, none for the weekday of DUE
+    days: Vec<Weekday>,
+    /// Where the weeks counted by INTERVAL start when there are days
+    week_start: Weekday,
+    /// The occurrences left, including the one that is due
+    count: Option<u32>,
+    until: Option<NaiveDate>,
+}
+
+impl Rule {
+    /// None when the rule has parts that aren't followed, such as BYMONTHDAY or the BYDAY of
+    /// a MONTHLY rule
+    fn parse(rule: &str) -> Option<Rule> {
+        let mut parsed = Rule {
+            frequency: String::new(),
+            interval: 1,
+            days: Vec::new(),
+            week_start: Weekday::Mon,
+            count: None,
+            until: None,
+        };
+        for part in rule.split(';') {
+            match part.split_once('=')? {
+                ("FREQ", value) => parsed.frequency = value.to_owned(),
+                ("INTERVAL", value) => parsed.interval = value.parse().ok().filter(|i| *i > 0)?,
+                ("BYDAY", value) => {
+                    parsed.days = value.split(',').map(weekday).collect::<Option<_>>()?
+                }
+                ("WKST", value) => parsed.week_start = weekday(value)?,
+                ("COUNT", value) => parsed.count = Some(value.parse().ok()?),
+                ("UNTIL", value) => {
+                    let date = NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d");
+                    parsed.until = Some(date.ok()?)
+                }
+                _ => return None,
+            }
+        }
+
+        let followed = ["DAILY", "WEEKLY", "MONTHLY", "YEARLY"].contains(&&*parsed.frequency)
+            && (parsed.days.is_empty() || parsed.frequency == "WEEKLY");
+        followed.then_some(parsed)
+    }
+
+    /// The next or previous date in the series, months without the day, i.e. the 31st, are
+    /// skipped as RFC 5545 asks
+    fn step(&self, date: NaiveDate, forward: bool) -> Option<NaiveDate> {
+        let sign = if forward { 1 } else { -1 };
+        let months = match &*self.frequency {
+            "DAILY" => return add_days(date, sign * self.interval as i64),
+            "WEEKLY" if self.days.is_empty() => {
+                return add_days(date, sign * 7 * self.interval as i64)
+            }
+            "WEEKLY" => {
+                // Days from the start of the week, the rest of this week is tried first
+                let offset = (7 + date.weekday().num_days_from_monday()
+                    - self.week_start.num_days_from_monday())
+                    % 7;
+                let offset = offset as i64;
+                let week_start = add_days(date, -offset)?;
+                let weeks = 7 * self.interval as i64;
+                let offsets: Vec<i64> = match forward {
+                    true => (offset + 1..7).chain(weeks..weeks + 7).collect(),
+                    false => (0..offset).rev().chain((-weeks..7 - weeks).rev()).collect(),
+                };
+                return offsets
+                    .into_iter()
+                    .filter_map(|days| add_days(week_start, days))
+                    .find(|day| self.days.contains(&day.weekday()));
+            }
+            "MONTHLY" => self.interval,
+            _ => self.interval * 12,
+        };
+
+        let first = date.with_day(1)?;
+        (1..=12).find_map(|count| {
+            let months = Months::new(months * count);
+            let month = match forward {
+                true => first.checked_add_months(months),
+                false => first.checked_sub_months(months),
+            };
+            month?.with_day(date.day())
+        })
+    }
+}
+
+/// "MO" to "SU" as in BYDAY, days with a number such as "1MO" are only for monthly rules
+fn weekday(day: &str) -> Option<Weekday> {
+    match day {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn add_days(date: NaiveDate, days: i64) -> Option<NaiveDate> {
+    date.checked_add_signed(Duration::days(days))
+}
+
+/// The VTODO moved one occurrence of its RRULE forward or back. DUE and DTSTART move by the
+/// same number of days with their time and TZID kept, and COUNT keeps counting the occurrences
+/// left. None when the rule can't be followed or the occurrence that is due is the last one.
+fn move_series(todo: &[String], rule: &str, forward: bool) -> Option<Vec<String>> {
+    let parsed = Rule::parse(rule)?;
+    if forward && parsed.count.is_some_and(|count| count <= 1) {
+        return None;
+    }
+    let date = |line: &str| NaiveDate::parse_from_str(property(line).2.get(..8)?, "%Y%m%d").ok();
+    let due = date(&get_line(todo, "DUE")?)?;
+    let next = parsed.step(due, forward)?;
+    if forward && parsed.until.is_some_and(|until| next > until) {
+        return None;
+    }
+
+    let mut moved = todo.to_vec();
+    for name in ["DUE", "DTSTART"] {
+        if let Some(line) = get_line(todo, name) {
+            let (_, params, value) = property(&line);
+            let date = date(&line)?.checked_add_signed(next - due)?;
+            let value = format!("{}{}", date.format("%Y%m%d"), &value[8..]);
+            let line = match params {
+                "" => format!("{name}:{value}"),
+                _ => format!("{name};{params}:{value}"),
+            };
+            set_property(&mut moved, name, Some(line));
+        }
+    }
+    if let Some(count) = parsed.count {
+        let count = if forward { count - 1 } else { count + 1 };
+        let rule: Vec<String> = rule
+            .split(';')
+            .map(|part| match part.starts_with("COUNT=") {
+                true => format!("COUNT={count}"),
+                false => part.to_owned(),
+            })
+            .collect();
+        set_property(
+            &mut moved,
+            "RRULE",
+            Some(format!("RRULE:{}", rule.join(";"))),
+        );
+    }
+    Some(moved)
+}
+
+/// Replace the property in a VTODO, or remove it when line is None.
+/// Properties of VALARMs within it are left alone.
+fn set_property(todo: &mut Vec<String>, name: &str, line: Option<String>) {
+    let mut depth = 0;
+    todo.retain(|existing| {
+        match property(existing) {
+            ("BEGIN", _, "VTODO") | ("END", _, "VTODO") => (),
+            ("BEGIN", _, _) => depth += 1,
+            ("END", _, _) => depth -= 1,
+            (existing_name, _, _) => return depth > 0 || existing_name != name,
+        }
+        true
+    });
+    if let Some(line) = line {
+        todo.insert(todo.len() - 1, line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{store, test};
+    use pretty_assertions::assert_eq;
+
+    const TODOS: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:plants\r\nSUMMARY:Water the plants\\, all \r\n of them\r\nPRIORITY:1\r\nDUE;TZID=Europe/Paris:20230131T090000\r\nRRULE:FREQ=WEEKLY\r\nCATEGORIES:garden,outside\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nDESCRIPTION:Reminder\r\nEND:VALARM\r\nEND:VTODO\r\nBEGIN:VTODO\r\nUID:can\r\nSUMMARY:Fill the can\r\nDUE;VALUE=DATE:20230130\r\nRELATED-TO:plants\r\nSTATUS:COMPLETED\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+
+    #[test]
+    fn parse_lines_maps_vtodos_onto_items() {
+        let items = parse_lines("Home", &unfold(TODOS));
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].id, "plants");
+        assert_eq!(items[0].content, "Water the plants, all of them");
+        assert_eq!(items[0].description, "");
+        assert_eq!(items[0].priority, 4);
+        assert_eq!(
+            items[0].labels,
+            vec![String::from("garden"), String::from("outside")]
+        );
+        assert_eq!(
+            items[0].due,
+            Some(DateInfo {
+                date: String::from("2023-01-31T09:00:00"),
+                is_recurring: true,
+                timezone: Some(String::from("Europe/Paris")),
+                string: String::from("FREQ=WEEKLY"),
+            })
+        );
+        assert!(items[1].checked);
+        assert_eq!(items[1].parent_id, Some(String::from("plants")));
+        assert_eq!(
+            items[1].due.as_ref().map(|due| due.date.clone()),
+            Some(String::from("2023-01-30"))
+        );
+    }
+
+    /// A vdirsyncer directory with a Home calendar holding TODOS
+    fn calendars(config: &Config) -> (Ics, PathBuf) {
+        let directory = store::path(config, "calendars");
+        fs::create_dir_all(directory.join("Home")).unwrap();
+        let home = directory.join("Home").join("plants.ics");
+        fs::write(&home, TODOS).unwrap();
+        let backend = Ics {
+            directory: directory.display().to_string(),
+        };
+        (backend, home)
+    }
+
+    #[test]
+    fn recurring_todos_move_on_and_keep_their_alarms() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let (backend, home) = calendars(&config);
+        assert_eq!(backend.items(&config, "Home").unwrap().len(), 1);
+
+        // Weekly, so it moves on a week rather than completing the series
+        backend.complete(&config, "plants").unwrap();
+        let text = fs::read_to_string(&home).unwrap();
+        assert!(text.contains("DUE;TZID=Europe/Paris:20230207T090000"));
+        assert!(!text.contains("COMPLETED:"));
+        assert!(text.contains("ACTION:DISPLAY\r\nDESCRIPTION:Reminder\r\nEND:VALARM"));
+        assert_eq!(backend.items(&config, "Home").unwrap().len(), 1);
+
+        backend.uncomplete(&config, "plants").unwrap();
+        let text = fs::read_to_string(&home).unwrap();
+        assert!(text.contains("DUE;TZID=Europe/Paris:20230131T090000"));
+
+        backend.update_due(&config, "plants", "2023-02-01").unwrap();
+        let text = fs::read_to_string(&home).unwrap();
+        assert!(text.contains("DUE;VALUE=DATE:20230201"));
+        assert!(!text.contains("TZID"));
+    }
+
+    #[test]
+    fn added_todos_get_a_file_of_their_own() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let (backend, _) = calendars(&config);
+
+        backend
+            .add(&config, "Work", "Review the report, then file it")
+            .unwrap();
+        assert_eq!(
+            backend.views(&config),
+            Ok(vec![String::from("Home"), String::from("Work")])
+        );
+        let work = backend.items(&config, "Work").unwrap();
+        assert_eq!(work.len(), 1);
+        assert_eq!(work[0].content, "Review the report, then file it");
+        let file = backend
+            .path()
+            .join("Work")
+            .join(format!("{}.ics", work[0].id));
+        let text = fs::read_to_string(&file).unwrap();
+        assert!(text.contains("SUMMARY:Review the report\\, then file it\r\n"));
+
+        backend.complete(&config, &work[0].id).unwrap();
+        let text = fs::read_to_string(&file).unwrap();
+        assert!(text.contains("STATUS:COMPLETED\r\nCOMPLETED:"));
+        assert_eq!(backend.items(&config, "Work"), Ok(Vec::new()));
+        backend.uncomplete(&config, &work[0].id).unwrap();
+        assert_eq!(backend.items(&config, "Work").unwrap().len(), 1);
+        assert_eq!(
+            backend.complete(&config, "missing"),
+            Err(Error::ItemNotFound(String::from("missing")))
+        );
+    }
+
+    #[test]
+    fn recurring_dates_step_through_the_series() {
+        let date = |text: &str| NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap();
+        let step = |rule: &str, text: &str, forward: bool| {
+            Rule::parse(rule).unwrap().step(date(text), forward)
+        };
+
+        assert_eq!(
+            step("FREQ=MONTHLY", "2023-01-31", true),
+            Some(date("2023-03-31"))
+        );
+        assert_eq!(
+            step("FREQ=MONTHLY", "2023-03-31", false),
+            Some(date("2023-01-31"))
+        );
+        assert_eq!(
+            step("FREQ=YEARLY", "2024-02-29", true),
+            Some(date("2028-02-29"))
+        );
+        assert_eq!(
+            step("FREQ=DAILY;INTERVAL=3", "2023-01-31", true),
+            Some(date("2023-02-03"))
+        );
+
+        // 2023-01-30 is a Monday
+        let weekly = "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE";
+        assert_eq!(step(weekly, "2023-01-30", true), Some(date("2023-02-01")));
+        assert_eq!(step(weekly, "2023-02-01", true), Some(date("2023-02-13")));
+        assert_eq!(step(weekly, "2023-02-13", false), Some(date("2023-02-01")));
+        assert_eq!(step(weekly, "2023-02-01", false), Some(date("2023-01-30")));
+        // Weeks starting on Sunday put the Monday after in the same week
+        let sunday = "FREQ=WEEKLY;INTERVAL=2;BYDAY=SU,MO;WKST=SU";
+        assert_eq!(step(sunday, "2023-01-29", true), Some(date("2023-01-30")));
+        assert_eq!(step(sunday, "2023-01-30", true), Some(date("2023-02-12")));
+
+        assert!(Rule::parse("FREQ=MONTHLY;BYDAY=1MO").is_none());
+        assert!(Rule::parse("FREQ=WEEKLY;BYDAY=1MO").is_none());
+        assert!(Rule::parse("FREQ=MONTHLY;BYMONTHDAY=15").is_none());
+    }
+
+    #[test]
+    fn recurring_todos_move_due_and_start_together() {
+        let todo = vec![
+            String::from("BEGIN:VTODO"),
+            String::from("DTSTART;VALUE=DATE:20230129"),
+            String::from("DUE;VALUE=DATE:20230131"),
+            String::from("END:VTODO"),
+        ];
+
+        let moved = move_series(&todo, "FREQ=WEEKLY;INTERVAL=2", true).unwrap();
+        assert_eq!(
+            get_line(&moved, "DTSTART"),
+            Some(String::from("DTSTART;VALUE=DATE:20230212"))
+        );
+        assert_eq!(
+            get_line(&moved, "DUE"),
+            Some(String::from("DUE;VALUE=DATE:20230214"))
+        );
+    }
+
+    #[test]
+    fn series_that_are_over_or_not_followed_are_completed() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let (backend, home) = calendars(&config);
+        let with_rule = |rule: &str| TODOS.replace("RRULE:FREQ=WEEKLY", rule);
+
+        fs::write(&home, with_rule("RRULE:FREQ=WEEKLY;COUNT=2")).unwrap();
+        backend.complete(&config, "plants").unwrap();
+        let text = fs::read_to_string(&home).unwrap();
+        assert!(text.contains("RRULE:FREQ=WEEKLY;COUNT=1"));
+        assert!(text.contains("DUE;TZID=Europe/Paris:20230207T090000"));
+        backend.complete(&config, "plants").unwrap();
+        let text = fs::read_to_string(&home).unwrap();
+        assert!(text.contains("STATUS:COMPLETED"));
+        assert!(text.contains("PERCENT-COMPLETE:100"));
+        assert!(text.contains("DUE;TZID=Europe/Paris:20230207T090000"));
+        assert_eq!(backend.items(&config, "Home"), Ok(Vec::new()));
+
+        // Reopened where it was left, rather than moved back
+        backend.uncomplete(&config, "plants").unwrap();
+        let text = fs::read_to_string(&home).unwrap();
+        assert!(text.contains("STATUS:NEEDS-ACTION"));
+        assert!(!text.contains("PERCENT-COMPLETE"));
+        assert!(text.contains("DUE;TZID=Europe/Paris:20230207T090000"));
+
+        fs::write(&home, with_rule("RRULE:FREQ=WEEKLY;UNTIL=20230205T000000Z")).unwrap();
+        backend.complete(&config, "plants").unwrap();
+        assert_eq!(backend.items(&config, "Home"), Ok(Vec::new()));
+
+        fs::write(&home, with_rule("RRULE:FREQ=MONTHLY;BYMONTHDAY=15")).unwrap();
+        backend.complete(&config, "plants").unwrap();
+        assert_eq!(backend.items(&config, "Home"), Ok(Vec::new()));
+    }
+}
@@ -3,19 +3,37 @@ use chrono::NaiveDate;
 use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use std::cmp::Reverse;
+use std::fmt;
 
 use crate::config::Config;
+use crate::error::Error;
 use crate::time;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct Item {
     pub id: String,
+    #[serde(default)]
+    pub project_id: String,
     pub content: String,
     pub priority: u8,
     pub checked: bool,
     pub description: String,
     pub due: Option<DateInfo>,
     pub is_deleted: bool,
+    /// When the item was created, i.e. "2022-03-01T10:00:00.000000Z"
+    #[serde(default)]
+    pub added_at: Option<String>,
+    /// Label names, without the @
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// The item this is a subtask of
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub section_id: Option<String>,
+    /// Position among the parent's subtasks, lowest first
+    #[serde(default)]
+    pub child_order: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
@@ -23,11 +41,57 @@ pub struct DateInfo {
     pub date: String,
     pub is_recurring: bool,
     pub timezone: Option<String>,
+    /// How the date was written, i.e. "every monday"
+    #[serde(default)]
+    pub string: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Body {
-    items: Vec<Item>,
+/// How an item's value for sorting adds up, each part weighted by the scoring in config
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Score {
+    /// For having no due date or being due today
+    pub date: u32,
+    /// For having a due date that doesn't repeat
+    pub not_recurring: u32,
+    pub priority: u32,
+    /// For being due before today
+    pub overdue: u32,
+    /// For being due close to now
+    pub time_window: u32,
+    /// For the item's labels, negative when they push it back
+    pub labels: i64,
+}
+
+impl Score {
+    pub fn total(&self) -> u32 {
+        let bonuses = [
+            self.date,
+            self.not_recurring,
+            self.priority,
+            self.overdue,
+            self.time_window,
+        ]
+        .map(i64::from)
+        .iter()
+        .sum::<i64>();
+        (bonuses + self.labels).clamp(0, u32::MAX as i64) as u32
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "date {}\nnot recurring {}\npriority {}\noverdue {}\ntime window {}\nlabels {}\ntotal {}",
+            self.date,
+            self.not_recurring,
+            self.priority,
+            self.overdue,
+            self.time_window,
+            self.labels,
+            self.total()
+        )
+    }
 }
 
 enum DateTimeInfo {
@@ -43,7 +107,16 @@ enum DateTimeInfo {
 }
 
 impl Item {
-    pub fn fmt(&self, config: &Config) -> String {
+    /// Format with the content of the parent item and the name of the section, when there are any
+    pub fn fmt(&self, config: &Config, parent: Option<&str>, section: Option<&str>) -> String {
+        let parent = match parent {
+            Some(parent) => format!("{parent} › "),
+            None => String::new(),
+        };
+        let section = match section {
+            Some(section) => format!("\nSection: {section}"),
+            None => String::new(),
+        };
         let description = match &*self.description {
             "" => String::from(""),
             _ => format!("\n{}", self.description),
@@ -66,70 +139,119 @@ impl Item {
                 format!("\nDue: {datetime_string}{recurring_icon}")
             }
             Ok(DateTimeInfo::NoDateTime) => String::from(""),
-            Err(string) => string.clone(),
+            Err(error) => format!("\n{error}"),
         };
 
-        format!("\n{}{}{}", self.content, description, due)
-    }
-
-    /// Determines the numeric value of an item for sorting
-    fn value(&self, config: &Config) -> u32 {
-        let date_value: u8 = self.date_value(config);
-        let priority_value: u8 = self.priority_value();
+        let labels: String = self
+            .labels
+            .iter()
+            .map(|label| format!(" @{label}"))
+            .collect();
 
-        date_value as u32 + priority_value as u32
+        format!(
+            "\n{}{}{}{}{}{}",
+            parent, self.content, labels, description, due, section
+        )
     }
 
-    /// Return the value of the due field
-    fn date_value(&self, config: &Config) -> u8 {
+    /// Determines the value of an item for sorting, and how it adds up
+    pub fn value(&self, config: &Config) -> Score {
+        let scoring = &config.scoring;
+        let priority = self.priority_value(config);
+        let labels = self.label_value(config);
+        let not_recurring = |is_recurring: bool| match is_recurring {
+            true => 0,
+            false => scoring.not_recurring,
+        };
+
         match &self.datetimeinfo(config) {
-            Ok(DateTimeInfo::NoDateTime) => 80,
+            Ok(DateTimeInfo::NoDateTime) => Score {
+                date: scoring.no_date,
+                priority,
+                labels,
+                ..Score::default()
+            },
             Ok(DateTimeInfo::Date { date, is_recurring }) => {
                 let today_value = if *date == time::today_date(config) {
-                    100
+                    scoring.today
+                } else {
+                    0
+                };
+                let overdue = if self.is_overdue(config) {
+                    scoring.overdue
                 } else {
                     0
                 };
-                let overdue_value = if self.is_overdue(config) { 150 } else { 0 };
-                let recurring_value = if is_recurring.to_owned() { 0 } else { 50 };
-                today_value + overdue_value + recurring_value
+                Score {
+                    date: today_value,
+                    not_recurring: not_recurring(*is_recurring),
+                    priority,
+                    overdue,
+                    time_window: 0,
+                    labels,
+                }
             }
             Ok(DateTimeInfo::DateTime {
                 datetime,
                 is_recurring,
             }) => {
-                let recurring_value = if is_recurring.to_owned() { 0 } else { 50 };
                 let duration = *datetime - time::now(config);
-                match duration.num_minutes() {
-                    -15..=15 => 200 + recurring_value,
-                    _ => recurring_value,
+                let time_window = if duration.num_minutes().abs() <= scoring.time_window_minutes {
+                    scoring.time_window
+                } else {
+                    0
+                };
+                Score {
+                    date: 0,
+                    not_recurring: not_recurring(*is_recurring),
+                    priority,
+                    overdue: 0,
+                    time_window,
+                    labels,
                 }
             }
-            Err(_) => 50,
+            Err(_) => Score {
+                not_recurring: scoring.not_recurring,
+                priority,
+                labels,
+                ..Score::default()
+            },
         }
     }
 
-    fn priority_value(&self) -> u8 {
+    fn label_value(&self, config: &Config) -> i64 {
+        self.labels
+            .iter()
+            .filter_map(|label| config.scoring.labels.get(label))
+            .sum()
+    }
+
+    /// True if the item has a label that config says to never show
+    pub fn is_excluded(&self, config: &Config) -> bool {
+        self.labels
+            .iter()
+            .any(|label| config.exclude_labels.contains(label))
+    }
+
+    fn priority_value(&self, config: &Config) -> u32 {
         match self.priority {
-            2 => 1,
-            3 => 3,
-            4 => 4,
-            _ => 2,
+            1..=4 => config.scoring.priority[self.priority as usize - 1],
+            _ => config.scoring.priority[0],
         }
     }
 
     /// Converts the JSON date representation into Date or Datetime
-    fn datetimeinfo(&self, config: &Config) -> Result<DateTimeInfo, String> {
+    fn datetimeinfo(&self, config: &Config) -> Result<DateTimeInfo, Error> {
         let tz = match (self.clone().due, config.clone().timezone) {
-            (None, Some(tz_string)) => time::timezone_from_str(&Some(tz_string)),
+            (None, Some(tz_string)) => time::timezone_from_str(&Some(tz_string))?,
             (None, None) => Tz::UTC,
-            (Some(DateInfo { timezone: None, .. }), Some(tz_string)) => time::timezone_from_str(&Some(tz_string)),
+            (Some(DateInfo { timezone: None, .. }), Some(tz_string)) => time::timezone_from_str(&Some(tz_string))?,
             (Some(DateInfo { timezone: None, .. }), None) => Tz::UTC,
             (Some(DateInfo {
                 timezone: Some(tz_string),
                 ..
                 // Remove the Some here
-            }), _) => time::timezone_from_str(&Some(tz_string)),
+            }), _) => time::timezone_from_str(&Some(tz_string))?,
         };
         match self.clone().due {
             None => Ok(DateTimeInfo::NoDateTime),
@@ -148,6 +270,18 @@ impl Item {
         }
     }
 
+    /// When the item is due, a date without a time counts from the start of that day
+    pub fn due_datetime(&self, config: &Config) -> Option<DateTime<Tz>> {
+        match self.datetimeinfo(config) {
+            Ok(DateTimeInfo::NoDateTime) | Err(_) => None,
+            Ok(DateTimeInfo::Date { date, .. }) => {
+                let tz = time::timezone_from_str(&config.timezone).ok()?;
+                date.and_hms_opt(0, 0, 0)?.and_local_timezone(tz).earliest()
+            }
+            Ok(DateTimeInfo::DateTime { datetime, .. }) => Some(datetime),
+        }
+    }
+
     fn has_no_date(&self) -> bool {
         self.due.is_none()
     }
@@ -175,20 +309,40 @@ impl Item {
         }
     }
 }
-pub fn json_to_items(json: String) -> Result<Vec<Item>, String> {
-    let result: Result<Body, _> = serde_json::from_str(&json);
-    match result {
-        Ok(body) => Ok(body.items),
-        Err(err) => Err(format!("Could not parse response for item: {err:?}")),
-    }
+pub fn sort_by_value(mut items: Vec<Item>, config: &Config) -> Vec<Item> {
+    items.sort_by_key(|b| Reverse(b.value(config).total()));
+    items
 }
 
-pub fn sort_by_value(mut items: Vec<Item>, config: &Config) -> Vec<Item> {
-    items.sort_by_key(|b| Reverse(b.value(config)));
+/// Parents with open subtasks are left out, their subtasks are done first
+pub fn filter_open_parents(items: Vec<Item>) -> Vec<Item> {
+    let parent_ids: Vec<String> = items
+        .iter()
+        .filter_map(|item| item.parent_id.clone())
+        .collect();
+
+    items
+        .into_iter()
+        .filter(|item| !parent_ids.contains(&item.id))
+        .collect()
+}
+
+/// Only the first open subtask of each parent is kept, by child_order
+pub fn filter_later_subtasks(items: Vec<Item>) -> Vec<Item> {
     items
+        .iter()
+        .filter(|item| match &item.parent_id {
+            None => true,
+            Some(parent_id) => !items.iter().any(|sibling| {
+                sibling.parent_id.as_ref() == Some(parent_id)
+                    && sibling.child_order < item.child_order
+            }),
+        })
+        .cloned()
+        .collect()
 }
 
-pub fn filter_not_in_future(items: Vec<Item>, config: &Config) -> Result<Vec<Item>, String> {
+pub fn filter_not_in_future(items: Vec<Item>, config: &Config) -> Result<Vec<Item>, Error> {
     let items = items
         .into_iter()
         .filter(|item| item.is_today(config) || item.has_no_date() || item.is_overdue(config))
@@ -200,14 +354,22 @@ pub fn filter_not_in_future(items: Vec<Item>, config: &Config) -> Result<Vec<Ite
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Scoring;
     use crate::test;
     use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    /// The value of the due field
+    fn date_value(item: &Item, config: &Config) -> u32 {
+        let score = item.value(config);
+        score.total() - score.priority
+    }
 
     #[test]
     fn date_value_can_handle_date() {
         let config = test::helpers::config_fixture();
         // On another day
-        assert_eq!(test::helpers::item_fixture().date_value(&config), 50);
+        assert_eq!(date_value(&test::helpers::item_fixture(), &config), 50);
 
         // Recurring
         let item = Item {
@@ -217,7 +379,7 @@ mod tests {
             }),
             ..test::helpers::item_fixture()
         };
-        assert_eq!(item.date_value(&config), 0);
+        assert_eq!(date_value(&item, &config), 0);
 
         // Overdue
         let item = Item {
@@ -225,14 +387,15 @@ mod tests {
                 date: String::from("2001-11-13"),
                 is_recurring: true,
                 timezone: Some(String::from("America/Los_Angeles")),
+                string: String::new(),
             }),
             ..test::helpers::item_fixture()
         };
-        assert_eq!(item.date_value(&config), 150);
+        assert_eq!(date_value(&item, &config), 150);
 
         // No date
         let item = Item { due: None, ..item };
-        assert_eq!(item.date_value(&config), 80);
+        assert_eq!(date_value(&item, &config), 80);
     }
 
     #[test]
@@ -246,7 +409,7 @@ mod tests {
             ..test::helpers::item_fixture()
         };
 
-        assert_eq!(item.date_value(&config), 50);
+        assert_eq!(date_value(&item, &config), 50);
     }
 
     #[test]
@@ -263,7 +426,7 @@ mod tests {
 
         let output = "\nGet gifts for the twins\nDue: 2021-08-13";
 
-        assert_eq!(format!("{}", item.fmt(&config)), output);
+        assert_eq!(format!("{}", item.fmt(&config, None, None)), output);
     }
 
     #[test]
@@ -280,7 +443,7 @@ mod tests {
 
         let output = "\nGet gifts for the twins\nDue: Today";
 
-        assert_eq!(format!("{}", item.fmt(&config)), output);
+        assert_eq!(format!("{}", item.fmt(&config, None, None)), output);
     }
 
     #[test]
@@ -288,7 +451,126 @@ mod tests {
         let config = test::helpers::config_fixture();
         let item = test::helpers::item_fixture();
 
-        assert_eq!(item.value(&config), 53);
+        assert_eq!(item.value(&config).total(), 53);
+    }
+
+    #[test]
+    fn sort_by_value_follows_the_scoring_in_config() {
+        let config = test::helpers::config_fixture();
+        let due = |date: &str| {
+            Some(DateInfo {
+                date: String::from(date),
+                is_recurring: false,
+                timezone: None,
+                string: String::new(),
+            })
+        };
+        let no_date = Item {
+            id: String::from("1"),
+            due: None,
+            priority: 1,
+            ..test::helpers::item_fixture()
+        };
+        let today = Item {
+            id: String::from("2"),
+            due: due(&time::today_string(&config)),
+            priority: 4,
+            ..test::helpers::item_fixture()
+        };
+        let overdue = Item {
+            id: String::from("3"),
+            due: due("2020-12-20"),
+            priority: 2,
+            ..test::helpers::item_fixture()
+        };
+
+        // The weights that used to be hardcoded
+        assert_eq!(no_date.value(&config).total(), 80 + 2);
+        assert_eq!(today.value(&config).total(), 100 + 50 + 4);
+        assert_eq!(overdue.value(&config).total(), 150 + 50 + 1);
+        let input = vec![no_date.clone(), today.clone(), overdue.clone()];
+        let expected = vec![overdue.clone(), today.clone(), no_date.clone()];
+        assert_eq!(sort_by_value(input.clone(), &config), expected);
+
+        let config = Config {
+            scoring: Scoring {
+                no_date: 500,
+                priority: [0, 0, 0, 100],
+                ..Scoring::default()
+            },
+            ..config
+        };
+        let expected = vec![no_date, today, overdue];
+        assert_eq!(sort_by_value(input, &config), expected);
+    }
+
+    #[test]
+    fn value_breaks_down_the_score() {
+        let config = test::helpers::config_fixture();
+        let item = Item {
+            due: Some(DateInfo {
+                date: String::from("2001-11-13"),
+                is_recurring: true,
+                timezone: None,
+                string: String::new(),
+            }),
+            ..test::helpers::item_fixture()
+        };
+
+        let score = item.value(&config);
+        let expected = Score {
+            date: 0,
+            not_recurring: 0,
+            priority: 3,
+            overdue: 150,
+            time_window: 0,
+            labels: 0,
+        };
+        assert_eq!(score, expected);
+        assert_eq!(
+            score.to_string(),
+            "date 0\nnot recurring 0\npriority 3\noverdue 150\ntime window 0\nlabels 0\ntotal 153"
+        );
+    }
+
+    #[test]
+    fn labels_are_weighted_excluded_and_shown() {
+        let config = Config {
+            scoring: Scoring {
+                labels: HashMap::from([
+                    (String::from("quick"), 40),
+                    (String::from("waiting"), -100),
+                ]),
+                ..Scoring::default()
+            },
+            exclude_labels: vec![String::from("someday")],
+            ..test::helpers::config_fixture()
+        };
+        let item = Item {
+            labels: vec![String::from("quick"), String::from("home")],
+            due: None,
+            ..test::helpers::item_fixture()
+        };
+
+        assert_eq!(item.value(&config).labels, 40);
+        assert_eq!(item.value(&config).total(), 80 + 3 + 40);
+        assert_eq!(
+            item.fmt(&config, None, None),
+            "\nGet gifts for the twins @quick @home"
+        );
+        assert!(!item.is_excluded(&config));
+
+        let waiting = Item {
+            labels: vec![String::from("waiting")],
+            ..item.clone()
+        };
+        assert_eq!(waiting.value(&config).total(), 0);
+
+        let someday = Item {
+            labels: vec![String::from("someday")],
+            ..item
+        };
+        assert!(someday.is_excluded(&config));
     }
 
     #[test]
@@ -326,6 +608,7 @@ mod tests {
                 date: time::today_string(&config),
                 is_recurring: false,
                 timezone: None,
+                string: String::new(),
             }),
             ..test::helpers::item_fixture()
         };
@@ -336,12 +619,30 @@ mod tests {
                 date: String::from("2021-09-06T16:00:00"),
                 is_recurring: false,
                 timezone: None,
+                string: String::new(),
             }),
             ..test::helpers::item_fixture()
         };
         assert!(!item_in_past.is_today(&config));
     }
 
+    #[test]
+    fn filter_not_in_future_keeps_timed_items_due_later_today() {
+        let config = test::helpers::config_fixture();
+        let later_today = Item {
+            due: Some(DateInfo {
+                date: format!("{}T23:59:59", time::today_string(&config)),
+                is_recurring: false,
+                timezone: None,
+                string: String::new(),
+            }),
+            ..test::helpers::item_fixture()
+        };
+
+        let result = filter_not_in_future(vec![later_today.clone()], &config);
+        assert_eq!(result, Ok(vec![later_today]));
+    }
+
     #[test]
     fn sort_by_value_works() {
         let config = test::helpers::config_fixture();
@@ -350,6 +651,7 @@ mod tests {
                 date: time::today_string(&config),
                 is_recurring: false,
                 timezone: None,
+                string: String::new(),
             }),
             ..test::helpers::item_fixture()
         };
@@ -359,6 +661,7 @@ mod tests {
                 date: time::today_string(&config),
                 is_recurring: false,
                 timezone: None,
+                string: String::new(),
             }),
             ..test::helpers::item_fixture()
         };
@@ -368,6 +671,7 @@ mod tests {
                 date: String::from("2035-12-12"),
                 is_recurring: false,
                 timezone: None,
+                string: String::new(),
             }),
             ..test::helpers::item_fixture()
         };
@@ -383,12 +687,18 @@ mod tests {
         let config = test::helpers::config_fixture();
         let item = Item {
             id: String::from("222"),
+            project_id: String::from("2203306141"),
             content: String::from("Get gifts for the twins"),
             checked: false,
             description: String::from(""),
             due: None,
             priority: 3,
             is_deleted: false,
+            added_at: None,
+            labels: Vec::new(),
+            parent_id: None,
+            section_id: None,
+            child_order: 0,
         };
 
         assert!(!item.is_overdue(&config));
@@ -398,6 +708,7 @@ mod tests {
                 date: time::today_string(&config),
                 is_recurring: false,
                 timezone: None,
+                string: String::new(),
             }),
             ..item.clone()
         };
@@ -408,6 +719,7 @@ mod tests {
                 date: String::from("2035-12-12"),
                 is_recurring: false,
                 timezone: None,
+                string: String::new(),
             }),
             ..item.clone()
         };
@@ -418,16 +730,10 @@ mod tests {
                 date: String::from("2020-12-20"),
                 is_recurring: false,
                 timezone: None,
+                string: String::new(),
             }),
             ..item
         };
         assert!(item_today.is_overdue(&config));
     }
-
-    #[test]
-    fn json_to_items_works() {
-        let json = String::from("2{.e");
-        let error_text = String::from("Could not parse response for item: Error(\"invalid type: integer `2`, expected struct Body\", line: 1, column: 1)");
-        assert_eq!(json_to_items(json), Err(error_text));
-    }
 }
@@ -2,17 +2,39 @@
 
 use core::time::Duration;
 use eframe::egui;
-use std::sync::mpsc;
-use std::thread;
+use std::time::Instant;
 
+mod backend;
+mod cache;
 mod config;
+mod error;
+mod ics;
 mod items;
+mod markdown;
+mod outbox;
 mod projects;
+mod ranking;
 mod request;
+mod snoozed;
+mod store;
+#[cfg(test)]
+mod stub_server;
 mod test;
 mod time;
+mod todo_txt;
+mod todoist;
+mod worker;
 
-use config::Config;
+use error::Error;
+use projects::Candidate;
+use time::Snooze;
+use worker::{Command, Response, Worker};
+
+/// How long an action can be undone for
+const UNDO_WINDOW: Duration = Duration::from_secs(10);
+
+/// How many actions are kept for undoing
+const UNDO_LIMIT: usize = 5;
 
 fn main() {
     // Log to stdout (if you run with `RUST_LOG=debug`).
@@ -32,36 +54,199 @@ fn main() {
 
 #[derive(Clone)]
 enum State {
-    // Clear the screen and prepare to fetch
-    BeginFetch,
-    // Spawn a thread and fetch in the background
+    // Waiting on the worker, show a spinner
     Fetching,
     // Fetch is complete, show the results
     DoneFetch { text: Option<String> },
+    // Pick how long to snooze the current task for
+    ChooseSnooze { text: String },
+    // Type a task to add, text is the task to go back to
+    Adding { text: Option<String>, input: String },
+    // Something went wrong, show the message and offer a retry
+    Error { message: String },
+}
+
+/// Something that was sent to Todoist and can be taken back
+#[derive(Clone)]
+enum Action {
+    Completed {
+        project: String,
+        id: String,
+        at: Instant,
+    },
+}
+
+impl Action {
+    fn remaining(&self) -> Option<Duration> {
+        match self {
+            Action::Completed { at, .. } => UNDO_WINDOW.checked_sub(at.elapsed()),
+        }
+    }
 }
 
 struct MyApp {
+    // Projects in the bottom bar
     projects: Vec<String>,
+    // Projects picked in config, before hiding
+    visible_projects: Vec<String>,
+    // Every project in config and the Todoist account
+    all_projects: Vec<String>,
+    // Hidden until the app is restarted
+    hidden_projects: Vec<String>,
     project: String,
     state: State,
-    tx: mpsc::Sender<Option<String>>,
-    rx: mpsc::Receiver<Option<String>>,
+    worker: Worker,
+    // Commands waiting to be sent to Todoist
+    pending: usize,
+    // Ids of items passed over this session
+    skipped: Vec<String>,
+    // Recent actions that can be undone, most recent last
+    undo: Vec<Action>,
+    // The project the candidates were ranked for, and the candidates best first
+    candidates: (String, Vec<Candidate>),
+    // How many candidates to list after the current one
+    up_next: usize,
+    // Shown when adding a task, in the syntax of the backend
+    add_example: &'static str,
 }
 
 impl Default for MyApp {
     fn default() -> Self {
-        let projects = projects();
+        let ((all_projects, projects), state) = match projects() {
+            Ok(projects) => (projects, State::Fetching),
+            Err(e) => ((Vec::new(), Vec::new()), State::from(Err(e))),
+        };
         let project = get_first_project(projects.clone());
-        let text = get_next(project.clone());
-        let (tx, rx) = mpsc::channel();
 
-        Self {
-            state: State::DoneFetch { text },
-            projects,
+        let mut app = Self {
+            state,
+            visible_projects: projects.clone(),
+            projects: with_all_projects(projects),
+            all_projects,
+            hidden_projects: Vec::new(),
             project,
-            tx,
-            rx,
+            worker: Worker::spawn(None),
+            pending: 0,
+            skipped: Vec::new(),
+            undo: Vec::new(),
+            candidates: (String::new(), Vec::new()),
+            up_next: 0,
+            add_example: add_example(),
+        };
+        if let State::Fetching = app.state {
+            app.fetch_next();
         }
+        app
+    }
+}
+
+impl MyApp {
+    /// Hand a command to the worker and show the spinner until it responds
+    fn send(&mut self, command: Command) {
+        self.worker.send(command);
+        self.state = State::Fetching;
+    }
+
+    fn fetch_next(&mut self) {
+        if self.projects.is_empty() {
+            // Nothing to show until a sync finds some projects
+            self.send(Command::Refresh);
+        } else {
+            self.send(Command::Next {
+                project: self.project.clone(),
+                skipped: self.skipped.clone(),
+            });
+        }
+    }
+
+    fn set_projects(&mut self, all: Vec<String>, visible: Vec<String>) {
+        self.all_projects = all;
+        self.projects = with_all_projects(visible.clone())
+            .into_iter()
+            .filter(|project| !self.hidden_projects.contains(project))
+            .collect();
+        self.visible_projects = visible;
+
+        if !self.projects.contains(&self.project) {
+            self.project = get_first_project(self.projects.clone());
+            if self.projects.is_empty() {
+                self.state = State::DoneFetch { text: None };
+            } else {
+                self.fetch_next();
+            }
+        }
+    }
+
+    fn toggle_project(&mut self, project: &str, visible: bool) {
+        let selected: Vec<String> = self
+            .all_projects
+            .iter()
+            .filter(|p| match *p == project {
+                true => visible,
+                false => self.visible_projects.contains(p),
+            })
+            .cloned()
+            .collect();
+
+        self.worker.send(Command::SelectProjects(selected.clone()));
+        self.set_projects(self.all_projects.clone(), selected);
+    }
+
+    fn complete(&mut self) {
+        self.send(Command::Complete {
+            project: self.project.clone(),
+            skipped: self.skipped.clone(),
+        });
+    }
+
+    fn snooze(&mut self, snooze: Snooze) {
+        self.send(Command::Snooze {
+            project: self.project.clone(),
+            snooze,
+            skipped: self.skipped.clone(),
+        });
+    }
+
+    fn skip(&mut self) {
+        self.send(Command::Skip {
+            project: self.project.clone(),
+            skipped: self.skipped.clone(),
+        });
+    }
+
+    fn add(&mut self, text: String) {
+        self.send(Command::Add {
+            project: self.project.clone(),
+            text,
+            skipped: self.skipped.clone(),
+        });
+    }
+
+    fn undo(&mut self) {
+        match self.undo.pop() {
+            Some(Action::Completed { project, id, .. }) => {
+                self.project = project.clone();
+                self.send(Command::Undo {
+                    project,
+                    id,
+                    skipped: self.skipped.clone(),
+                });
+            }
+            None => (),
+        }
+    }
+
+    fn promote(&mut self, id: String) {
+        self.send(Command::Promote {
+            project: self.project.clone(),
+            id,
+            skipped: self.skipped.clone(),
+        });
+    }
+
+    fn reset_skips(&mut self) {
+        self.skipped.clear();
+        self.fetch_next();
     }
 }
 
@@ -69,54 +254,223 @@ impl Default for MyApp {
 #[allow(clippy::collapsible_if)]
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Responses for a project that is no longer selected are stale
+        while let Some(response) = self.worker.try_recv() {
+            match response {
+                Response::Next { project, result } if project == self.project => {
+                    self.state = State::from(result);
+                }
+                Response::Next { .. } => (),
+                Response::Candidates {
+                    project,
+                    candidates,
+                    up_next,
+                } => {
+                    self.candidates = (project, candidates);
+                    self.up_next = up_next;
+                }
+                Response::Skipped(id) => self.skipped.push(id),
+                Response::Completed { project, id } => {
+                    self.undo.push(Action::Completed {
+                        project,
+                        id,
+                        at: Instant::now(),
+                    });
+                    let excess = self.undo.len().saturating_sub(UNDO_LIMIT);
+                    self.undo.drain(..excess);
+                }
+                Response::Pending(pending) => self.pending = pending,
+                Response::Projects { all, visible } => self.set_projects(all, visible),
+                // Not while picking a snooze or typing a new task
+                Response::Changed => {
+                    if matches!(self.state, State::DoneFetch { .. } | State::Error { .. }) {
+                        self.fetch_next();
+                    }
+                }
+                Response::Failed(e) => self.state = State::from(Err(e)),
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
                 ui.label("The One Thing");
-                ui.label(String::new());
+                if self.pending > 0 {
+                    ui.small(format!("⇅ {} waiting to sync", self.pending));
+                } else {
+                    ui.label(String::new());
+                }
 
-                ui.vertical_centered(|ui| match self.state.clone() {
-                    State::BeginFetch => {
-                        ui.add(egui::Spinner::new());
-                        spawn_complete_task(self.project.clone(), self.tx.clone());
-                        self.state = State::Fetching;
+                self.undo.retain(|action| action.remaining().is_some());
+                if let Some(remaining) = self.undo.last().and_then(Action::remaining) {
+                    ui.horizontal(|ui| {
+                        ui.small(format!(
+                            "Completed, undo within {}s",
+                            remaining.as_secs() + 1
+                        ));
+                        if ui.small_button("Undo ↶").clicked() {
+                            self.undo();
+                        }
+                    });
+                    // Typing a task to add should not undo anything
+                    let typing = matches!(self.state, State::Adding { .. });
+                    if !typing && ui.input(|i| i.key_pressed(egui::Key::U)) {
+                        self.undo();
                     }
+                }
 
+                ui.vertical_centered(|ui| match self.state.clone() {
                     State::Fetching => {
                         ui.add(egui::Spinner::new());
-
-                        if let Ok(text) = self.rx.try_recv() {
-                            self.state = State::DoneFetch { text };
-                        }
                     }
 
                     State::DoneFetch { text } => {
-                        if let Some(text) = text {
-                            ui.heading(text);
+                        if let Some(text) = text.clone() {
+                            ui.heading(text.clone());
                             ui.label(String::new());
                             if ui.button("Complete ✔").clicked() {
-                                self.state = State::BeginFetch;
+                                self.complete();
+                            }
+                            if ui.button("Snooze ⏰").clicked() {
+                                self.state = State::ChooseSnooze { text: text.clone() };
+                            }
+                            if ui.button("Skip ⏭").clicked() {
+                                self.skip();
                             }
                             ui.label(String::new());
                             if ui.input(|i| i.key_pressed(egui::Key::C)) {
-                                self.state = State::BeginFetch;
+                                self.complete();
+                            }
+                            if ui.input(|i| i.key_pressed(egui::Key::S)) {
+                                self.state = State::ChooseSnooze { text };
+                            }
+                            if ui.input(|i| i.key_pressed(egui::Key::N)) {
+                                self.skip();
                             }
                         } else {
                             ui.heading(String::from("\nNo tasks remaining"));
                             ui.label(String::new());
                             ui.label(String::new());
                         };
+                        if !self.skipped.is_empty()
+                            && ui
+                                .button(format!("Reset {} skipped ↺", self.skipped.len()))
+                                .clicked()
+                        {
+                            self.reset_skips();
+                        }
+                        if ui.button("Add Task ＋").clicked()
+                            || ui.input(|i| i.key_pressed(egui::Key::A))
+                        {
+                            self.state = State::Adding {
+                                text: text.clone(),
+                                input: String::new(),
+                            };
+                        }
                         if ui.button("Hide Project 🗙").clicked() {
                             hide(self.project.clone(), self);
                         }
                         if ui.input(|i| i.key_pressed(egui::Key::H)) {
                             hide(self.project.clone(), self);
                         }
+                        if text.is_some() && self.candidates.0 == self.project {
+                            let up_next = self.candidates.1.iter().skip(1).take(self.up_next);
+                            let mut promoted = None;
+                            if up_next.len() > 0 {
+                                ui.label(String::new());
+                                ui.small("Up next");
+                            }
+                            for candidate in up_next {
+                                if ui.small_button(&candidate.content).clicked() {
+                                    promoted = Some(candidate.id.clone());
+                                }
+                            }
+                            if let Some(id) = promoted {
+                                self.promote(id);
+                            }
+                            // Other rankings don't add up the scoring in config
+                            let candidates = &self.candidates.1;
+                            if candidates.iter().all(|candidate| candidate.score.is_some()) {
+                                ui.collapsing("Why this task?", |ui| {
+                                    for candidate in candidates {
+                                        ui.label(egui::RichText::new(&candidate.content).strong());
+                                        if let Some(score) = &candidate.score {
+                                            ui.small(score.to_string());
+                                        }
+                                    }
+                                });
+                            }
+                        }
+                    }
+
+                    State::ChooseSnooze { text } => {
+                        ui.heading(text.clone());
+                        ui.label(String::new());
+                        let keys = [
+                            egui::Key::Num1,
+                            egui::Key::Num2,
+                            egui::Key::Num3,
+                            egui::Key::Num4,
+                        ];
+                        for (num, (snooze, key)) in Snooze::ALL.iter().zip(keys).enumerate() {
+                            let label = format!("{} {}", num + 1, snooze.label());
+                            if ui.button(label).clicked() {
+                                self.snooze(*snooze);
+                            }
+                            if ui.input(|i| i.key_pressed(key)) {
+                                self.snooze(*snooze);
+                            }
+                        }
+                        if ui.button("Cancel").clicked()
+                            || ui.input(|i| i.key_pressed(egui::Key::Escape))
+                        {
+                            self.state = State::DoneFetch { text: Some(text) };
+                        }
+                    }
+
+                    State::Adding { text, mut input } => {
+                        ui.label(format!(
+                            "Add to {}, i.e. \"{}\"",
+                            self.project, self.add_example
+                        ));
+                        let response = ui.text_edit_singleline(&mut input);
+                        response.request_focus();
+                        if ui.input(|i| i.key_pressed(egui::Key::Enter)) && !input.trim().is_empty()
+                        {
+                            self.add(input);
+                        } else if ui.button("Cancel").clicked()
+                            || ui.input(|i| i.key_pressed(egui::Key::Escape))
+                        {
+                            self.state = State::DoneFetch { text };
+                        } else {
+                            self.state = State::Adding { text, input };
+                        }
+                    }
+
+                    State::Error { message } => {
+                        ui.heading("Something went wrong");
+                        ui.label(message);
+                        ui.label(String::new());
+                        if ui.button("Retry ↻").clicked() {
+                            self.fetch_next();
+                        }
+                        if ui.input(|i| i.key_pressed(egui::Key::R)) {
+                            self.fetch_next();
+                        }
+                    }
+                });
+
+                ui.collapsing("Projects", |ui| {
+                    for project in self.all_projects.clone() {
+                        let mut visible = self.visible_projects.contains(&project);
+                        if ui.checkbox(&mut visible, &project).changed() {
+                            self.toggle_project(&project, visible);
+                        }
                     }
                 });
             });
 
             ui.with_layout(egui::Layout::left_to_right(egui::Align::BOTTOM), |ui| {
-                for project in self.projects.iter() {
+                for project in self.projects.clone().iter() {
                     if *project.clone() == self.project {
                         if ui.add_enabled(false, egui::Button::new(project)).clicked() {
                             unreachable!();
@@ -124,9 +478,7 @@ impl eframe::App for MyApp {
                     } else {
                         if ui.button(project).clicked() {
                             self.project = project.to_string();
-                            self.state = State::DoneFetch {
-                                text: get_next(self.project.clone()),
-                            };
+                            self.fetch_next();
                         }
                     }
                 }
@@ -136,44 +488,31 @@ impl eframe::App for MyApp {
     }
 }
 
-fn projects() -> Vec<String> {
-    match config::get_or_create(None) {
-        Ok(Config { projects, .. }) => {
-            let mut projects = projects
-                .keys()
-                .map(|k| k.to_owned())
-                .collect::<Vec<String>>();
-
-            projects.sort();
-            projects
+impl From<Result<Option<String>, Error>> for State {
+    fn from(result: Result<Option<String>, Error>) -> Self {
+        match result {
+            Ok(text) => State::DoneFetch { text },
+            Err(e) => State::Error {
+                message: e.to_string(),
+            },
         }
-        Err(e) => vec![e],
     }
 }
 
-fn get_next(project: String) -> Option<String> {
-    match config::get_or_create(None) {
-        Ok(config) => projects::next_item(config, &project).unwrap(),
-        Err(_e) => None,
-    }
-}
-
-fn spawn_complete_task(project: String, tx: mpsc::Sender<Option<String>>) {
-    thread::spawn(|| complete(project, tx));
+/// All known projects and those to show in the bottom bar, from config and the last sync
+fn projects() -> Result<(Vec<String>, Vec<String>), Error> {
+    let config = config::get_or_create(None)?;
+    Ok((
+        projects::all_names(&config)?,
+        projects::visible_names(&config)?,
+    ))
 }
 
-fn complete(project: String, tx: mpsc::Sender<Option<String>>) {
-    match config::get_or_create(None) {
-        Ok(config) => {
-            request::complete_item(config).unwrap();
-            match get_next(project) {
-                Some(text) => tx.send(Some(text)),
-                None => tx.send(None),
-            }
-        }
-        Err(_e) => tx.send(None),
-    }
-    .unwrap();
+/// An example task for the backend in config
+fn add_example() -> &'static str {
+    config::get_or_create(None)
+        .map(|config| config.backend.task_backend().add_example())
+        .unwrap_or_else(|_| backend::Backend::default().task_backend().add_example())
 }
 
 fn hide(project: String, state: &mut MyApp) {
@@ -184,13 +523,19 @@ fn hide(project: String, state: &mut MyApp) {
         .filter(|s| s != &project)
         .collect();
 
-    let project = get_first_project(projects.clone());
-
+    state.worker.send(Command::HideProject(project.clone()));
+    state.hidden_projects.push(project);
+    state.project = get_first_project(projects.clone());
     state.projects = projects;
-    state.project = project.clone();
-    state.state = State::DoneFetch {
-        text: get_next(project),
-    };
+    state.fetch_next();
+}
+
+/// Adds the pseudo-project that ranks every project together, when there is more than one
+fn with_all_projects(mut projects: Vec<String>) -> Vec<String> {
+    if projects.len() > 1 {
+        projects.push(String::from(projects::ALL_PROJECTS));
+    }
+    projects
 }
 
 fn get_first_project(projects: Vec<String>) -> String {
@@ -0,0 +1,435 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::backend::TaskBackend;
+use crate::config::Config;
+use crate::error::Error;
+use crate::items::{DateInfo, Item};
+use crate::projects::ALL_PROJECTS;
+use crate::{store, time};
+
+/// Where items added to "All projects" go
+const INBOX: &str = "Inbox";
+
+/// Marks the due date that follows it, as the Obsidian Tasks plugin does
+const DUE_EMOJI: &str = "📅";
+
+/// Checklists in a directory of Markdown files, one file per project named after it.
+/// Ids are the project, line number and a hash of the item's text, i.e. "Home:12:5f0c2a1e",
+/// so that a line moved by an edit in another editor isn't taken for another item.
+pub struct Markdown {
+    pub directory: String,
+}
+
+impl TaskBackend for Markdown {
+    /// Nothing to fetch, notes edited in another app are read as they are on disk
+    fn refresh(&self, _config: &Config, _view: Option<&str>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn views(&self, _config: &Config) -> Result<Vec<String>, Error> {
+        let mut names: Vec<String> = self
+            .files()?
+            .iter()
+            .filter_map(|path| path.file_stem())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+
+        names.sort();
+        Ok(names)
+    }
+
+    /// Every open item for "All projects", except those in the files of hidden projects
+    fn items(&self, config: &Config, view: &str) -> Result<Vec<Item>, Error> {
+        let projects = match view {
+            ALL_PROJECTS => self
+                .views(config)?
+                .into_iter()
+                .filter(|project| !config.hidden_projects.contains(project))
+                .collect(),
+            _ => vec![view.to_owned()],
+        };
+        let mut items = Vec::new();
+        for project in projects {
+            let lines = store::read_lines(&self.path(&project))?;
+            items.extend(parse_lines(&project, &lines));
+        }
+
+        Ok(items.into_iter().filter(|item| !item.checked).collect())
+    }
+
+    /// The directory is watched as well, so that a new note shows up as a project
+    fn modified(&self, _config: &Config) -> Option<SystemTime> {
+        let mut paths = self.files().ok()?;
+        paths.push(PathBuf::from(&self.directory));
+        store::modified(&paths)
+    }
+
+    fn complete(&self, _config: &Config, id: &str) -> Result<(), Error> {
+        self.update_line(id, |line| set_checkbox(line, "[x]"))
+    }
+
+    fn uncomplete(&self, _config: &Config, id: &str) -> Result<(), Error> {
+        self.update_line(id, |line| set_checkbox(line, "[ ]"))
+    }
+
+    fn update_due(&self, _config: &Config, id: &str, date: &str) -> Result<(), Error> {
+        self.update_line(id, |line| {
+            let words: Vec<&str> = line.split(' ').collect();
+            let mut updated = Vec::new();
+            let mut replaced = false;
+            for (index, word) in words.iter().enumerate() {
+                let previous = index.checked_sub(1).map(|index| words[index]);
+                if previous == Some(DUE_EMOJI) && time::is_date(word) {
+                    updated.push(date.to_owned());
+                    replaced = true;
+                } else if word.starts_with("due:") && time::is_date(&word[4..]) {
+                    updated.push(format!("due:{date}"));
+                    replaced = true;
+                } else {
+                    updated.push(word.to_string());
+                }
+            }
+            if !replaced {
+                updated.push(format!("{DUE_EMOJI} {date}"));
+            }
+            updated.join(" ")
+        })
+    }
+
+    /// Appended to the view's file, or to Inbox.md for "All projects"
+    fn add(&self, _config: &Config, view: &str, text: &str) -> Result<(), Error> {
+        let project = match view {
+            ALL_PROJECTS => INBOX,
+            _ => view,
+        };
+        let path = self.path(project);
+        let mut lines = store::read_lines(&path)?;
+        lines.push(format!("- [ ] {}", text.trim()));
+        store::write_lines(&path, lines)
+    }
+
+    fn add_example(&self) -> &'static str {
+        "Call Bob 📅 2023-01-31 ⏫ #phone"
+    }
+
+    /// Adds the item it is nested in and the heading above it, and the note it is from in
+    /// "All projects"
+    fn fmt_item(&self, config: &Config, item: &Item, view: &str) -> Result<String, Error> {
+        let lines = store::read_lines(&self.path(&item.project_id))?;
+        let parent = parse_lines(&item.project_id, &lines)
+            .into_iter()
+            .find(|parent| Some(&parent.id) == item.parent_id.as_ref())
+            .map(|parent| parent.content);
+
+        let text = item.fmt(config, parent.as_deref(), item.section_id.as_deref());
+        match view {
+            ALL_PROJECTS => Ok(format!("{text}\nProject: {}", item.project_id)),
+            _ => Ok(text),
+        }
+    }
+}
+
+impl Markdown {
+    fn path(&self, project: &str) -> PathBuf {
+        Path::new(&self.directory).join(format!("{project}.md"))
+    }
+
+    /// The notes in the directory, other files such as attachments are left out
+    fn files(&self) -> Result<Vec<PathBuf>, Error> {
+        Ok(store::read_dir(Path::new(&self.directory))?
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|extension| extension == "md"))
+            .collect())
+    }
+
+    /// Change the line the id points at, as long as it still holds the same item
+    fn update_line(&self, id: &str, update: impl FnOnce(&str) -> String) -> Result<(), Error> {
+        let not_found = || Error::ItemNotFound(id.to_owned());
+        let (project, number) = id
+            .rsplit_once(':')
+            .and_then(|(rest, _)| rest.rsplit_once(':'))
+            .ok_or_else(not_found)?;
+        let number = number.parse::<usize>().map_err(|_| not_found())?;
+        let path = self.path(project);
+        let mut lines = store::read_lines(&path)?;
+        let line = number
+            .checked_sub(1)
+            .and_then(|index| lines.get_mut(index))
+            .filter(|line| {
+                checkbox(line).is_some_and(|(_, _, text)| item_id(project, number, text) == id)
+            })
+            .ok_or_else(not_found)?;
+        *line = update(line);
+        store::write_lines(&path, lines)
+    }
+}
+
+/// Checklist items in the lines of a file, nested items are subtasks of the one above
+/// and the last heading is the section.
+pub fn parse_lines(project: &str, lines: &[String]) -> Vec<Item> {
+    let mut items = Vec::new();
+    let mut section: Option<String> = None;
+    // Indentation and id of the items that later items may be nested in
+    let mut parents: Vec<(usize, String)> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(heading) = heading(line) {
+            section = Some(heading.to_owned());
+            parents.clear();
+            continue;
+        }
+        let Some((indent, checked, text)) = checkbox(line) else {
+            continue;
+        };
+
+        while parents.last().is_some_and(|(parent, _)| *parent >= indent) {
+            parents.pop();
+        }
+        let id = item_id(project, index + 1, text);
+        let parent_id = parents.last().map(|(_, id)| id.clone());
+        let child_order = items
+            .iter()
+            .filter(|item: &&Item| item.parent_id == parent_id)
+            .count() as i64;
+        parents.push((indent, id.clone()));
+
+        items.push(Item {
+            id,
+            project_id: project.to_owned(),
+            checked,
+            parent_id,
+            section_id: section.clone(),
+            child_order,
+            ..parse_text(text)
+        });
+    }
+
+    items
+}
+
+/// Checking the item or moving its due date leaves the content, and so the id, alone
+fn item_id(project: &str, number: usize, text: &str) -> String {
+    let content = parse_text(text).content;
+    format!("{project}:{number}:{}", store::fingerprint(&content))
+}
+
+/// The text of a "## Heading" line, a "#tag" at the start of a line is not one
+fn heading(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    match line.len() - text.len() {
+        1..=6 if text.is_empty() || text.starts_with(' ') => Some(text.trim()),
+        _ => None,
+    }
+}
+
+/// The line with the box of a "- [ ] text" line replaced, the text is left as it is
+fn set_checkbox(line: &str, checkbox_text: &str) -> String {
+    match checkbox(line) {
+        // After the indentation and "- "
+        Some((indent, _, _)) => {
+            let start = indent + 2;
+            format!("{}{checkbox_text}{}", &line[..start], &line[start + 3..])
+        }
+        None => line.to_owned(),
+    }
+}
+
+/// The indentation, whether it is checked and the text of a "- [ ] text" line
+fn checkbox(line: &str) -> Option<(usize, bool, &str)> {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    let rest = trimmed
+        .strip_prefix("- ")
+        .or_else(|| trimmed.strip_prefix("* "))?;
+    match rest.get(..4) {
+        Some("[ ] ") => Some((indent, false, &rest[4..])),
+        Some("[x] " | "[X] ") => Some((indent, true, &rest[4..])),
+        _ => None,
+    }
+}
+
+/// An item with the content, due date, priority and #tags from its text filled in.
+/// Due dates are "📅 2023-01-31" or "due:2023-01-31", priorities p1 to p4 or the
+/// Obsidian Tasks markers 🔺 ⏫ 🔼 🔽.
+fn parse_text(text: &str) -> Item {
+    let mut content = Vec::new();
+    let mut due = None;
+    let mut priority = 1;
+    let mut labels = Vec::new();
+
+    let mut words = text.split_whitespace().peekable();
+    while let Some(word) = words.next() {
+        let date = match word {
+            DUE_EMOJI => words.next_if(|date| time::is_date(date)),
+            _ => word.strip_prefix("due:").filter(|date| time::is_date(date)),
+        };
+        if let Some(date) = date {
+            due = Some(DateInfo {
+                date: date.to_owned(),
+                is_recurring: false,
+                timezone: None,
+                string: String::new(),
+            });
+            continue;
+        }
+
+        match word {
+            "p1" | "🔺" => priority = 4,
+            "p2" | "⏫" => priority = 3,
+            "p3" | "🔼" => priority = 2,
+            "p4" | "🔽" => priority = 1,
+            _ if word.len() > 1 && word.starts_with('#') => labels.push(word[1..].to_owned()),
+            _ => content.push(word),
+        }
+    }
+
+    Item {
+        id: String::new(),
+        project_id: String::new(),
+        content: content.join(" "),
+        priority,
+        checked: false,
+        description: String::new(),
+        due,
+        is_deleted: false,
+        added_at: None,
+        labels,
+        parent_id: None,
+        section_id: None,
+        child_order: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test;
+    use crate::test::helpers::ids;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn parse_lines_reads_checklists_with_subtasks_and_sections() {
+        let items = parse_lines(
+            "Home",
+            &lines(
+                "Notes that are not tasks\n## Garden\n- [ ] Water the plants 📅 2023-01-05 ⏫ #outside\n#not-a-heading\n  - [x] Fill the can\n  - [ ] Find the hose due:2023-01-04\n- [ ] Mow p1",
+            ),
+        );
+
+        assert_eq!(items.len(), 4);
+        let id = format!("Home:3:{}", store::fingerprint("Water the plants"));
+        assert_eq!(items[0].id, id);
+        assert_eq!(items[0].content, "Water the plants");
+        assert_eq!(items[0].priority, 3);
+        assert_eq!(items[0].labels, vec![String::from("outside")]);
+        assert_eq!(items[0].section_id, Some(String::from("Garden")));
+        assert_eq!(
+            items[0].due.as_ref().map(|due| due.date.clone()),
+            Some(String::from("2023-01-05"))
+        );
+        assert!(items[1].checked);
+        assert_eq!(items[1].parent_id, Some(id));
+        assert_eq!(items[2].child_order, 1);
+        assert_eq!(items[3].parent_id, None);
+        assert_eq!(items[3].priority, 4);
+    }
+
+    #[test]
+    fn set_checkbox_leaves_brackets_in_the_text_alone() {
+        let line = "  * [X] Check box [X] on form [ ]";
+        assert_eq!(
+            set_checkbox(line, "[ ]"),
+            "  * [ ] Check box [X] on form [ ]"
+        );
+        assert_eq!(
+            set_checkbox("- [ ] Tick [ ] and [x]", "[x]"),
+            "- [x] Tick [ ] and [x]"
+        );
+        assert_eq!(set_checkbox("Not [ ] a task", "[x]"), "Not [ ] a task");
+    }
+
+    fn notes(config: &Config) -> Markdown {
+        let directory = store::path(config, "notes");
+        fs::create_dir_all(&directory).unwrap();
+        Markdown {
+            directory: directory.display().to_string(),
+        }
+    }
+
+    #[test]
+    fn due_dates_are_moved_in_the_style_they_were_written() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let backend = notes(&config);
+        let home = backend.path("Home");
+        fs::write(
+            &home,
+            "- [ ] Pay rent due:2023-01-05\n- [ ] Water the plants 📅 2023-01-05 ⏫\n- [ ] Call Bob\n",
+        )
+        .unwrap();
+
+        for id in ids(&backend, &config, "Home") {
+            backend.update_due(&config, &id, "2023-02-01").unwrap();
+        }
+        assert_eq!(
+            fs::read_to_string(&home).unwrap(),
+            "- [ ] Pay rent due:2023-02-01\n- [ ] Water the plants 📅 2023-02-01 ⏫\n- [ ] Call Bob 📅 2023-02-01\n"
+        );
+    }
+
+    #[test]
+    fn checking_off_edits_only_its_own_line() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let backend = notes(&config);
+        let home = backend.path("Home");
+        fs::write(
+            &home,
+            "# Chores\n- [ ] Water the plants\n  - [ ] Fill the can\n",
+        )
+        .unwrap();
+        let ids = ids(&backend, &config, "Home");
+
+        backend.complete(&config, &ids[1]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&home).unwrap(),
+            "# Chores\n- [ ] Water the plants\n  - [x] Fill the can\n"
+        );
+        backend.uncomplete(&config, &ids[1]).unwrap();
+
+        // A line added above by another editor moves the item
+        let moved = "# Chores\n- [ ] Call Bob\n- [ ] Water the plants\n";
+        fs::write(&home, moved).unwrap();
+        assert_eq!(
+            backend.complete(&config, &ids[0]),
+            Err(Error::ItemNotFound(ids[0].clone()))
+        );
+        assert_eq!(fs::read_to_string(&home).unwrap(), moved);
+    }
+
+    #[test]
+    fn items_added_to_all_projects_go_to_the_inbox_note() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let backend = notes(&config);
+        fs::write(backend.path("Home"), "- [ ] Water the plants\n").unwrap();
+        fs::write(Path::new(&backend.directory).join("photo.png"), "").unwrap();
+
+        backend.add(&config, ALL_PROJECTS, " Call Bob ").unwrap();
+        assert_eq!(
+            backend.views(&config),
+            Ok(vec![String::from("Home"), String::from("Inbox")])
+        );
+        assert_eq!(
+            fs::read_to_string(backend.path(INBOX)).unwrap(),
+            "- [ ] Call Bob\n"
+        );
+
+        let items = backend.items(&config, ALL_PROJECTS).unwrap();
+        let text = backend.fmt_item(&config, &items[0], ALL_PROJECTS).unwrap();
+        assert!(text.ends_with("Project: Home"));
+    }
+}
@@ -0,0 +1,211 @@
+use serde_json::Value;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::request;
+use crate::request::SyncCommand;
+use crate::store;
+
+const FILENAME: &str = "tot_outbox.json";
+
+/// Sync commands that have not reached Todoist yet, kept in a file next to config
+pub fn path(config: &Config) -> PathBuf {
+    store::path(config, FILENAME)
+}
+
+pub fn load(config: &Config) -> Result<Vec<SyncCommand>, Error> {
+    store::load(&path(config))
+}
+
+fn save(config: &Config, commands: &[SyncCommand]) -> Result<(), Error> {
+    store::save(&path(config), &commands)
+}
+
+/// Add a command to the end of the queue
+pub fn push(config: &Config, command: SyncCommand) -> Result<(), Error> {
+    let mut commands = load(config)?;
+    commands.push(command);
+    save(config, &commands)
+}
+
+/// The ids of items with an item_close that has not been sent yet
+pub fn pending_closes(config: &Config) -> Result<Vec<String>, Error> {
+    let ids = load(config)?
+        .into_iter()
+        .filter(|command| command.kind == "item_close")
+        .filter_map(|command| command.args["id"].as_str().map(String::from))
+        .collect();
+
+    Ok(ids)
+}
+
+/// Take an item_close for the item out of the queue, returns false if it has already been sent
+pub fn cancel_close(config: &Config, id: &str) -> Result<bool, Error> {
+    let mut commands = load(config)?;
+    let count = commands.len();
+    commands.retain(|command| !(command.kind == "item_close" && command.args["id"] == id));
+    save(config, &commands)?;
+
+    Ok(commands.len() < count)
+}
+
+/// Send everything in the queue, in order, and return how many commands are still waiting.
+/// Replaying is safe because Todoist skips any uuid it has already applied.
+pub fn flush(config: &Config) -> Result<usize, Error> {
+    let (remaining, rejected) = send_all(config)?;
+    match rejected.into_iter().next() {
+        Some((_, error)) => Err(error),
+        None => Ok(remaining),
+    }
+}
+
+/// Like flush, but only a rejection of the command with the uuid is returned as an error,
+/// older commands that are rejected are dropped without blaming the command just sent
+pub fn flush_command(config: &Config, uuid: &str) -> Result<usize, Error> {
+    let (remaining, rejected) = send_all(config)?;
+    match rejected
+        .into_iter()
+        .find(|(rejected_uuid, _)| rejected_uuid == uuid)
+    {
+        Some((_, error)) => Err(error),
+        None => Ok(remaining),
+    }
+}
+
+/// How many commands are still waiting, and the errors for rejected commands by uuid
+fn send_all(config: &Config) -> Result<(usize, Vec<(String, Error)>), Error> {
+    let commands = load(config)?;
+    if commands.is_empty() {
+        return Ok((0, Vec::new()));
+    }
+
+    let response = request::sync_commands(config, &commands)?;
+    let mut rejected = Vec::new();
+    let remaining: Vec<SyncCommand> = commands
+        .into_iter()
+        .filter(|command| match response.sync_status.get(&command.uuid) {
+            None => true,
+            Some(Value::String(status)) if status == "ok" => false,
+            // Sending it again would only be rejected again
+            Some(error) => {
+                let error = Error::CommandRejected {
+                    command: command.kind.clone(),
+                    cause: error["error"].as_str().unwrap_or_default().to_owned(),
+                };
+                rejected.push((command.uuid.clone(), error));
+                false
+            }
+        })
+        .collect();
+
+    save(config, &remaining)?;
+
+    Ok((remaining.len(), rejected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub_server::{self, Reply, StubServer};
+    use crate::test;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn command(kind: &str, uuid: &str, id: &str) -> SyncCommand {
+        SyncCommand {
+            uuid: String::from(uuid),
+            ..SyncCommand::new(kind, json!({ "id": id }))
+        }
+    }
+
+    #[test]
+    fn completions_are_queued_while_offline_and_replayed() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let online = test::helpers::config_with_stub(&server);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let offline = Config {
+            todoist_url: Some(format!("http://{}", listener.local_addr().unwrap())),
+            ..online.clone()
+        };
+        drop(listener);
+
+        assert_eq!(request::complete_item(&offline, "6543210002"), Ok(()));
+        assert_eq!(
+            pending_closes(&offline),
+            Ok(vec![String::from("6543210002")])
+        );
+
+        assert_eq!(flush(&online), Ok(0));
+        assert_eq!(load(&online), Ok(Vec::new()));
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].body.contains("6543210002"));
+    }
+
+    #[test]
+    fn flush_keeps_unacknowledged_and_drops_rejected_commands() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        push(&config, command("item_close", "first", "1")).unwrap();
+        push(&config, command("item_close", "second", "2")).unwrap();
+        push(&config, command("item_close", "third", "3")).unwrap();
+        let body = json!({"sync_status": {
+            "first": "ok",
+            "second": {"error_code": 22, "error": "Item not found"}
+        }});
+        server.script(vec![Reply {
+            body: body.to_string(),
+            ..Reply::status(200)
+        }]);
+
+        assert_eq!(
+            flush(&config),
+            Err(Error::CommandRejected {
+                command: String::from("item_close"),
+                cause: String::from("Item not found")
+            })
+        );
+        assert_eq!(pending_closes(&config), Ok(vec![String::from("3")]));
+
+        let requests = server.requests();
+        let sent: Value = serde_json::from_str(&requests[0].body).unwrap();
+        let uuids: Vec<&str> = sent["commands"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|command| command["uuid"].as_str().unwrap())
+            .collect();
+        assert_eq!(uuids, vec!["first", "second", "third"]);
+    }
+
+    #[test]
+    fn flush_command_only_reports_its_own_rejection() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let rejected = json!({"error_code": 22, "error": "Item not found"});
+        push(&config, command("item_close", "older", "1")).unwrap();
+        push(&config, command("item_close", "newer", "2")).unwrap();
+        let body = json!({"sync_status": { "older": rejected, "newer": "ok" }});
+        server.script(vec![Reply {
+            body: body.to_string(),
+            ..Reply::status(200)
+        }]);
+
+        assert_eq!(flush_command(&config, "newer"), Ok(0));
+
+        push(&config, command("item_close", "newest", "3")).unwrap();
+        let body = json!({"sync_status": { "newest": rejected }});
+        server.script(vec![Reply {
+            body: body.to_string(),
+            ..Reply::status(200)
+        }]);
+        assert_eq!(
+            flush_command(&config, "newest"),
+            Err(Error::CommandRejected {
+                command: String::from("item_close"),
+                cause: String::from("Item not found")
+            })
+        );
+    }
+}
@@ -1,32 +1,414 @@
-use crate::config::Config;
-use crate::{items, projects, request};
+use serde::{Deserialize, Serialize};
 
-pub fn project_id(config: &Config, project_name: &str) -> Result<String, String> {
-    let project_id = config
+use crate::backend::TaskBackend;
+use crate::config::{Config, Subtasks};
+use crate::error::Error;
+use crate::items::{Item, Score};
+use crate::{cache, items, ranking, snoozed};
+
+/// A project in the Todoist account, found by syncing
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub is_archived: bool,
+    #[serde(default)]
+    pub is_deleted: bool,
+    /// Where Todoist puts items that don't name a project
+    #[serde(default)]
+    pub inbox_project: bool,
+}
+
+/// Not a Todoist project, ranks the items of every visible project together
+pub const ALL_PROJECTS: &str = "All projects";
+
+/// A section within a project, found by syncing
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Section {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub is_archived: bool,
+    #[serde(default)]
+    pub is_deleted: bool,
+}
+
+/// Looks in tod's config first, then at the projects from the last sync
+pub fn project_id(config: &Config, project_name: &str) -> Result<String, Error> {
+    if let Some(id) = config.projects.get(project_name) {
+        return Ok(id.to_owned());
+    }
+
+    cache::load(config)?
         .projects
-        .get(project_name)
-        .ok_or(format!(
-            "Project {project_name} not found, please add it to config"
-        ))?
-        .to_string();
+        .into_iter()
+        .find(|project| project.name == project_name)
+        .map(|project| project.id)
+        .ok_or_else(|| Error::ProjectNotFound(project_name.to_owned()))
+}
 
-    Ok(project_id)
+/// Every project and view name the backend offers, sorted
+pub fn all_names(config: &Config) -> Result<Vec<String>, Error> {
+    config.backend.task_backend().views(config)
 }
 
-/// Get the next item by priority and save its id to config
-pub fn next_item(config: Config, project_name: &str) -> Result<Option<String>, String> {
-    let project_id = projects::project_id(&config, project_name)?;
-    let items = request::items_for_project(&config, &project_id)?;
-    let filtered_items = items::filter_not_in_future(items, &config)?;
-    let maybe_item = items::sort_by_value(filtered_items, &config)
+/// The projects for the bottom bar, either those picked in config or all of them
+pub fn visible_names(config: &Config) -> Result<Vec<String>, Error> {
+    let names = all_names(config)?;
+    match &config.visible_projects {
+        Some(visible) => Ok(names
+            .into_iter()
+            .filter(|name| visible.contains(name))
+            .collect()),
+        None => Ok(names),
+    }
+}
+
+/// Refresh the backend, then get the best ranked item and save its id to config.
+/// Items with an id in skipped are passed over.
+pub fn next_item(
+    config: Config,
+    project_name: &str,
+    skipped: &[String],
+) -> Result<Option<String>, Error> {
+    config
+        .backend
+        .task_backend()
+        .refresh(&config, Some(project_name))?;
+    cached_next_item(config, project_name, skipped)
+}
+
+/// Get the best ranked item from the items as of the last refresh, without the network
+pub fn cached_next_item(
+    config: Config,
+    project_name: &str,
+    skipped: &[String],
+) -> Result<Option<String>, Error> {
+    let backend = config.backend.task_backend();
+    let maybe_item = ranked_items(&config, backend.as_ref(), project_name, skipped)?
         .first()
         .map(|item| item.to_owned());
 
     match maybe_item {
         Some(item) => {
             config.set_next_id(item.id.clone()).save()?;
-            Ok(Some(item.fmt(&config)))
+            Ok(Some(backend.fmt_item(&config, &item, project_name)?))
         }
         None => Ok(None),
     }
 }
+
+/// Show a particular item in the view instead of the best ranked one, and save its id to config
+pub fn promote_item(
+    config: Config,
+    project_name: &str,
+    id: &str,
+    skipped: &[String],
+) -> Result<Option<String>, Error> {
+    let backend = config.backend.task_backend();
+    let item = backend
+        .items(&config, project_name)?
+        .into_iter()
+        .find(|item| item.id == id);
+
+    match item {
+        Some(item) => {
+            config.set_next_id(item.id.clone()).save()?;
+            Ok(Some(backend.fmt_item(&config, &item, project_name)?))
+        }
+        // Gone since the list was shown
+        None => cached_next_item(config, project_name, skipped),
+    }
+}
+
+/// An item that could be shown, and why it ranks where it does
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Candidate {
+    pub id: String,
+    pub content: String,
+    /// None when the project's ranking doesn't follow the scoring in config
+    pub score: Option<Score>,
+}
+
+/// The item being shown followed by the best of the rest, without the network
+pub fn candidates(
+    config: &Config,
+    project_name: &str,
+    skipped: &[String],
+    count: usize,
+) -> Result<Vec<Candidate>, Error> {
+    let backend = config.backend.task_backend();
+    let mut items = ranked_items(config, backend.as_ref(), project_name, skipped)?;
+    // A random ranking would not pick the same item twice
+    if let Some(index) = items
+        .iter()
+        .position(|item| Some(&item.id) == config.next_id.as_ref())
+    {
+        let shown = items.remove(index);
+        items.insert(0, shown);
+    }
+
+    let ranker = ranking::ranker(config, project_name);
+    Ok(items
+        .into_iter()
+        .take(count)
+        .map(|item| Candidate {
+            score: ranker.score(&item, config),
+            id: item.id,
+            content: item.content,
+        })
+        .collect())
+}
+
+/// Items in the view that are due, best first
+fn ranked_items(
+    config: &Config,
+    backend: &dyn TaskBackend,
+    project_name: &str,
+    skipped: &[String],
+) -> Result<Vec<Item>, Error> {
+    let items = backend
+        .items(config, project_name)?
+        .into_iter()
+        .filter(|item| !item.is_excluded(config))
+        .collect();
+    // Skipped and snoozed subtasks still hold back their parent
+    let snoozed = snoozed::ids(config)?;
+    let mut items: Vec<Item> = items::filter_open_parents(items)
+        .into_iter()
+        .filter(|item| !skipped.contains(&item.id) && !snoozed.contains(&item.id))
+        .collect();
+    if config.subtasks == Subtasks::First {
+        items = items::filter_later_subtasks(items);
+    }
+    let filtered_items = items::filter_not_in_future(items, config)?;
+
+    Ok(ranking::rank(filtered_items, config, project_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend;
+    use crate::cache::Cache;
+    use crate::ranking::Ranking;
+    use crate::stub_server::{self, StubServer};
+    use crate::test;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    #[test]
+    fn next_item_and_complete_item_work_against_stub_server() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+
+        let text = next_item(config.clone(), "Home", &[]).unwrap();
+        let expected =
+            "\nPay the electricity bill\nAccount number is on the last statement\nDue: 2022-01-01";
+        assert_eq!(text, Some(String::from(expected)));
+
+        let config = Config::load(&config.path).unwrap();
+        assert_eq!(config.next_id, Some(String::from("6543210002")));
+        backend::complete_item(config.clone()).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/sync/v9/sync");
+        assert!(requests[0]
+            .body
+            .contains("\"resource_types\":[\"items\",\"projects\",\"sections\"]"));
+        assert_eq!(requests[1].path, "/sync/v9/sync");
+        assert!(requests[1].body.contains("\"item_close\""));
+        assert!(requests[1].body.contains("6543210002"));
+
+        // Completed items are gone from the cache before the next sync
+        let text = cached_next_item(config, "Home", &[]).unwrap();
+        assert_eq!(text, Some(String::from("\nWater the plants")));
+    }
+
+    #[test]
+    fn next_item_passes_over_skipped_items() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let skipped = vec![String::from("6543210002")];
+
+        let text = next_item(config.clone(), "Home", &skipped).unwrap();
+        assert_eq!(text, Some(String::from("\nWater the plants")));
+        let config = Config::load(&config.path).unwrap();
+        assert_eq!(config.next_id, Some(String::from("6543210001")));
+
+        let skipped = vec![String::from("6543210002"), String::from("6543210001")];
+        assert_eq!(cached_next_item(config, "Home", &skipped), Ok(None));
+    }
+
+    #[test]
+    fn parents_wait_for_their_subtasks() {
+        let config = test::helpers::save_to_temp_dir(Config {
+            projects: HashMap::from([(String::from("Home"), String::from("2203306141"))]),
+            ..test::helpers::config_fixture()
+        });
+        let item = |id: &str, content: &str, priority: u8, parent_id: Option<&str>| Item {
+            id: String::from(id),
+            content: String::from(content),
+            priority,
+            due: None,
+            parent_id: parent_id.map(String::from),
+            section_id: Some(String::from("7025")),
+            ..test::helpers::item_fixture()
+        };
+        let cache = Cache {
+            items: vec![
+                item("1", "Plan the garden", 4, None),
+                Item {
+                    child_order: 2,
+                    ..item("2", "Buy seeds", 4, Some("1"))
+                },
+                Item {
+                    child_order: 1,
+                    ..item("3", "Dig the beds", 1, Some("1"))
+                },
+            ],
+            sections: vec![Section {
+                id: String::from("7025"),
+                name: String::from("Garden"),
+                is_archived: false,
+                is_deleted: false,
+            }],
+            ..Cache::default()
+        };
+        std::fs::write(cache::path(&config), serde_json::to_string(&cache).unwrap()).unwrap();
+
+        let text = cached_next_item(config.clone(), "Home", &[]).unwrap();
+        let expected = "\nPlan the garden › Buy seeds\nSection: Garden";
+        assert_eq!(text, Some(String::from(expected)));
+
+        let config = Config {
+            subtasks: Subtasks::First,
+            ..config
+        };
+        let text = cached_next_item(config.clone(), "Home", &[]).unwrap();
+        let expected = "\nPlan the garden › Dig the beds\nSection: Garden";
+        assert_eq!(text, Some(String::from(expected)));
+
+        let skipped = vec![String::from("2"), String::from("3")];
+        assert_eq!(cached_next_item(config, "Home", &skipped), Ok(None));
+    }
+
+    #[test]
+    fn all_projects_ranks_every_project_together() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+
+        let text = next_item(config.clone(), ALL_PROJECTS, &[]).unwrap();
+        let expected = "\nPay the electricity bill\nAccount number is on the last statement\nDue: 2022-01-01\nProject: Home";
+        assert_eq!(text, Some(String::from(expected)));
+
+        let skipped = vec![String::from("6543210002")];
+        let text = cached_next_item(config, ALL_PROJECTS, &skipped).unwrap();
+        let expected = "\nReview the quarterly report\nDue: 2022-03-31\nProject: Work";
+        assert_eq!(text, Some(String::from(expected)));
+    }
+
+    #[test]
+    fn filters_rank_the_items_matching_a_query() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = Config {
+            filters: HashMap::from([(String::from("Urgent"), String::from("today & p1"))]),
+            ..test::helpers::config_with_stub(&server)
+        };
+
+        let text = next_item(config.clone(), "Urgent", &[]).unwrap();
+        let expected = "\nReview the quarterly report\nDue: 2022-03-31\nProject: Work";
+        assert_eq!(text, Some(String::from(expected)));
+        assert_eq!(
+            server.requests()[1].path,
+            "/rest/v2/tasks?filter=today+%26+p1"
+        );
+
+        let names = vec!["Home", "Inbox", "Urgent", "Work"];
+        assert_eq!(
+            all_names(&config),
+            Ok(names.into_iter().map(String::from).collect())
+        );
+
+        // Filters are left out of all projects, so nothing is counted twice
+        let skipped = vec![String::from("6543210002"), String::from("6543210004")];
+        let text = cached_next_item(config, ALL_PROJECTS, &skipped).unwrap();
+        assert_eq!(
+            text,
+            Some(String::from("\nWater the plants\nProject: Home"))
+        );
+    }
+
+    #[test]
+    fn promoted_items_are_shown_first() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        next_item(config.clone(), "Home", &[]).unwrap();
+
+        let text = promote_item(config.clone(), "Home", "6543210001", &[]).unwrap();
+        assert_eq!(text, Some(String::from("\nWater the plants")));
+
+        let config = Config::load(&config.path).unwrap();
+        assert_eq!(config.next_id, Some(String::from("6543210001")));
+        let ids: Vec<String> = candidates(&config, "Home", &[], 4)
+            .unwrap()
+            .into_iter()
+            .map(|candidate| candidate.id)
+            .collect();
+        assert_eq!(ids, vec!["6543210001", "6543210002"]);
+    }
+
+    #[test]
+    fn candidates_are_scored_only_by_the_default_ranking() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        cache::refresh(&config).unwrap();
+
+        let scored = candidates(&config, "Home", &[], 4).unwrap();
+        let items = cache::load(&config).unwrap().items;
+        let first = items.iter().find(|item| item.id == scored[0].id).unwrap();
+        assert_eq!(scored[0].score, Some(first.value(&config)));
+
+        let config = Config {
+            ranking: HashMap::from([(String::from("Home"), Ranking::Priority)]),
+            ..config
+        };
+        let unscored = candidates(&config, "Home", &[], 4).unwrap();
+        assert!(unscored.iter().all(|candidate| candidate.score.is_none()));
+    }
+
+    #[test]
+    fn projects_are_discovered_by_syncing() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = Config {
+            projects: HashMap::new(),
+            ..test::helpers::config_with_stub(&server)
+        };
+
+        assert_eq!(all_names(&config), Ok(Vec::new()));
+        assert_eq!(
+            project_id(&config, "Work"),
+            Err(Error::ProjectNotFound(String::from("Work")))
+        );
+
+        cache::refresh(&config).unwrap();
+
+        let names = vec!["Home", "Inbox", "Work"];
+        let names: Vec<String> = names.into_iter().map(String::from).collect();
+        assert_eq!(all_names(&config), Ok(names.clone()));
+        assert_eq!(visible_names(&config), Ok(names));
+        assert_eq!(project_id(&config, "Work"), Ok(String::from("2203306142")));
+        let text = next_item(config.clone(), "Work", &[]).unwrap();
+        assert_eq!(
+            text,
+            Some(String::from(
+                "\nReview the quarterly report\nDue: 2022-03-31"
+            ))
+        );
+
+        let config = config.set_visible_projects(vec![String::from("Work")]);
+        assert_eq!(visible_names(&config), Ok(vec![String::from("Work")]));
+    }
+}
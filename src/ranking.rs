@@ -0,0 +1,246 @@
+use chrono::Datelike;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+use crate::config::Config;
+use crate::items;
+use crate::items::{Item, Score};
+use crate::time;
+
+/// Decides which item is worth doing next
+pub trait Ranker {
+    /// Best item first
+    fn rank(&self, items: Vec<Item>, config: &Config) -> Vec<Item>;
+
+    /// How an item's rank adds up, None when the ranking isn't made of the scoring in config
+    fn score(&self, _item: &Item, _config: &Config) -> Option<Score> {
+        None
+    }
+}
+
+/// The ranking strategies that can be picked for a project in config
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ranking {
+    /// Weighs due dates, times and priority together
+    #[default]
+    Default,
+    /// Highest priority first
+    Priority,
+    /// Earliest due date first, items without a date last
+    DueDate,
+    /// Items that were added longest ago first
+    Oldest,
+    /// A random pick, weighted towards items the default ranking prefers. The same items
+    /// are ranked the same way until the next day.
+    WeightedRandom,
+}
+
+impl Ranking {
+    pub fn ranker(self) -> Box<dyn Ranker> {
+        match self {
+            Ranking::Default => Box::new(DefaultRanker),
+            Ranking::Priority => Box::new(PriorityRanker),
+            Ranking::DueDate => Box::new(DueDateRanker),
+            Ranking::Oldest => Box::new(OldestRanker),
+            Ranking::WeightedRandom => Box::new(WeightedRandomRanker),
+        }
+    }
+}
+
+/// The ranker for the strategy configured for the project
+pub fn ranker(config: &Config, project_name: &str) -> Box<dyn Ranker> {
+    config
+        .ranking
+        .get(project_name)
+        .copied()
+        .unwrap_or_default()
+        .ranker()
+}
+
+/// Rank items with the strategy configured for the project
+pub fn rank(items: Vec<Item>, config: &Config, project_name: &str) -> Vec<Item> {
+    ranker(config, project_name).rank(items, config)
+}
+
+pub struct DefaultRanker;
+
+impl Ranker for DefaultRanker {
+    fn score(&self, item: &Item, config: &Config) -> Option<Score> {
+        Some(item.value(config))
+    }
+
+    fn rank(&self, items: Vec<Item>, config: &Config) -> Vec<Item> {
+        items::sort_by_value(items, config)
+    }
+}
+
+pub struct PriorityRanker;
+
+impl Ranker for PriorityRanker {
+    /// Ties are broken by the default ranking
+    fn rank(&self, mut items: Vec<Item>, config: &Config) -> Vec<Item> {
+        items.sort_by_key(|item| Reverse((item.priority, item.value(config).total())));
+        items
+    }
+}
+
+pub struct DueDateRanker;
+
+impl Ranker for DueDateRanker {
+    /// A date comes before times on that day, ties are broken by priority
+    fn rank(&self, mut items: Vec<Item>, config: &Config) -> Vec<Item> {
+        items.sort_by_key(|item| {
+            let due = item.due_datetime(config);
+            (due.is_none(), due, Reverse(item.priority))
+        });
+        items
+    }
+}
+
+pub struct OldestRanker;
+
+impl Ranker for OldestRanker {
+    /// Ties are broken by priority
+    fn rank(&self, mut items: Vec<Item>, _config: &Config) -> Vec<Item> {
+        items.sort_by_key(|item| {
+            let added_at = item.added_at.clone();
+            (added_at.is_none(), added_at, Reverse(item.priority))
+        });
+        items
+    }
+}
+
+pub struct WeightedRandomRanker;
+
+impl WeightedRandomRanker {
+    /// Every item gets a chance, even those the default ranking values at 0
+    fn weight(item: &Item, config: &Config) -> u64 {
+        item.value(config).total() as u64 + 1
+    }
+}
+
+impl Ranker for WeightedRandomRanker {
+    /// Seeded with the day, so a sync or a skip doesn't reshuffle the items
+    fn rank(&self, mut items: Vec<Item>, config: &Config) -> Vec<Item> {
+        let day = time::today_date(config).num_days_from_ce();
+        let mut rng = StdRng::seed_from_u64(day as u64);
+        let mut ranked = Vec::with_capacity(items.len());
+
+        while !items.is_empty() {
+            let total: u64 = items.iter().map(|item| Self::weight(item, config)).sum();
+            let mut pick = rng.gen_range(0..total);
+            let index = items
+                .iter()
+                .position(|item| {
+                    let weight = Self::weight(item, config);
+                    if pick < weight {
+                        true
+                    } else {
+                        pick -= weight;
+                        false
+                    }
+                })
+                .unwrap_or_default();
+            ranked.push(items.remove(index));
+        }
+
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::DateInfo;
+    use crate::test;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn item(id: &str, priority: u8, date: Option<&str>, added_at: Option<&str>) -> Item {
+        Item {
+            id: String::from(id),
+            priority,
+            due: date.map(|date| DateInfo {
+                date: String::from(date),
+                is_recurring: false,
+                timezone: None,
+                string: String::new(),
+            }),
+            added_at: added_at.map(String::from),
+            ..test::helpers::item_fixture()
+        }
+    }
+
+    fn ids(items: Vec<Item>) -> Vec<String> {
+        items.into_iter().map(|item| item.id).collect()
+    }
+
+    #[test]
+    fn rankers_order_items_by_their_strategy() {
+        let config = test::helpers::config_fixture();
+        let items = vec![
+            item("1", 1, None, Some("2022-03-01T10:00:00Z")),
+            item("2", 4, Some("2099-02-01"), None),
+            item("3", 2, Some("2021-12-25"), Some("2021-01-01T10:00:00Z")),
+        ];
+
+        let ranked = |ranking: Ranking| ids(ranking.ranker().rank(items.clone(), &config));
+        assert_eq!(ranked(Ranking::Default), vec!["3", "1", "2"]);
+        assert_eq!(ranked(Ranking::Priority), vec!["2", "3", "1"]);
+        assert_eq!(ranked(Ranking::DueDate), vec!["3", "2", "1"]);
+        assert_eq!(ranked(Ranking::Oldest), vec!["3", "1", "2"]);
+
+        let mut random = ranked(Ranking::WeightedRandom);
+        assert_eq!(ranked(Ranking::WeightedRandom), random);
+        random.sort();
+        assert_eq!(random, vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn due_date_compares_the_moment_items_are_due() {
+        let config = Config {
+            timezone: Some(String::from("America/Vancouver")),
+            ..test::helpers::config_fixture()
+        };
+        let items = vec![
+            // 08:00 in Vancouver
+            item("1", 1, Some("2022-01-01T16:00:00Z"), None),
+            item("2", 1, Some("2022-01-01T09:00:00"), None),
+            item("3", 1, Some("2022-01-01"), None),
+            // 23:00 on the day before in Vancouver
+            item("4", 1, Some("2022-01-01T07:00:00Z"), None),
+            item("5", 1, Some("2022-01-02"), None),
+        ];
+
+        let ranked = ids(DueDateRanker.rank(items, &config));
+        assert_eq!(ranked, vec!["4", "3", "1", "2", "5"]);
+    }
+
+    #[test]
+    fn weighted_random_handles_large_values() {
+        let mut config = test::helpers::config_fixture();
+        config.scoring.no_date = u32::MAX / 2;
+        let items = vec![item("1", 1, None, None), item("2", 1, None, None)];
+
+        let ranked = WeightedRandomRanker.rank(items, &config);
+        assert_eq!(ranked.len(), 2);
+    }
+
+    #[test]
+    fn rank_uses_the_ranking_configured_for_the_project() {
+        let config = Config {
+            ranking: HashMap::from([(String::from("Work"), Ranking::Priority)]),
+            ..test::helpers::config_fixture()
+        };
+        let items = vec![
+            item("1", 1, Some("2021-12-25"), None),
+            item("2", 4, None, None),
+        ];
+
+        assert_eq!(ids(rank(items.clone(), &config, "Home")), vec!["1", "2"]);
+        assert_eq!(ids(rank(items, &config, "Work")), vec!["2", "1"]);
+    }
+}
@@ -1,65 +1,368 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
 use reqwest::blocking::Client;
 use reqwest::header::AUTHORIZATION;
 use reqwest::header::CONTENT_TYPE;
+use reqwest::header::RETRY_AFTER;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
 use uuid::Uuid;
 
-use crate::config::Config;
-use crate::items;
-use crate::items::Item;
+use crate::cache::{self, Cache};
+use crate::config::{Config, Network};
+use crate::error::Error;
+use crate::items::{DateInfo, Item};
+use crate::outbox;
+use crate::projects;
+use crate::projects::{Project, Section};
 
 // TODOIST URLS
-const PROJECT_DATA_URL: &str = "/sync/v9/projects/get_data";
+const TODOIST_URL: &str = "https://api.todoist.com";
 const SYNC_URL: &str = "/sync/v9/sync";
+const QUICK_ADD_URL: &str = "/sync/v9/quick/add";
+const TASKS_URL: &str = "/rest/v2/tasks";
+
+/// What the cache is made of, a sync token for other resource types doesn't cover them
+pub const RESOURCE_TYPES: [&str; 3] = ["items", "projects", "sections"];
 
 const FAKE_UUID: &str = "42963283-2bab-4b1f-bad2-278ef2b6ba2c";
 
-/// Get a vector of all items for a project
-pub fn items_for_project(config: &Config, project_id: &str) -> Result<Vec<Item>, String> {
-    let url = String::from(PROJECT_DATA_URL);
-    let body = json!({ "project_id": project_id });
-    let json = post_todoist_sync(config.token.clone(), url, body)?;
-    items::json_to_items(json)
+/// Overrides the base URL from config, i.e. for pointing at a local stub server
+const TODOIST_URL_ENV: &str = "TOT_TODOIST_URL";
+
+/// Longest Retry-After we will wait out, anything longer is returned as an error
+const MAX_RETRY_AFTER: u64 = 60;
+
+/// Shared so that connections are reused, rebuilt when the timeouts in config change
+static CLIENT: Mutex<Option<((u64, u64), Client)>> = Mutex::new(None);
+
+/// A mutating command for the sync API, Todoist ignores a uuid it has already seen
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SyncCommand {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub uuid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temp_id: Option<String>,
+    pub args: serde_json::Value,
+}
+
+impl SyncCommand {
+    pub fn new(kind: &str, args: serde_json::Value) -> SyncCommand {
+        SyncCommand {
+            kind: kind.to_owned(),
+            uuid: new_uuid(),
+            temp_id: None,
+            args,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct SyncResponse {
+    /// "ok" or an error object for each command uuid
+    #[serde(default)]
+    pub sync_status: HashMap<String, serde_json::Value>,
+    /// True when everything was sent, not just the changes since sync_token
+    #[serde(default)]
+    pub full_sync: bool,
+    pub sync_token: Option<String>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+    #[serde(default)]
+    pub projects: Vec<Project>,
+    #[serde(default)]
+    pub sections: Vec<Section>,
+}
+
+/// Get items, projects and sections changed since the sync token, "*" gets everything
+pub fn sync_resources(config: &Config, sync_token: &str) -> Result<SyncResponse, Error> {
+    let body = json!({ "sync_token": sync_token, "resource_types": RESOURCE_TYPES });
+    sync(config, body)
+}
+
+/// Complete an item, queued in the outbox if Todoist can't be reached
+pub fn complete_item(config: &Config, id: &str) -> Result<(), Error> {
+    let command = SyncCommand::new("item_close", json!({ "id": id }));
+    cache::remove_item(config, id)?;
+    send_command(config, command)
+}
+
+/// Undo the completion of an item, if the item_close is still queued it is never sent.
+/// Closing a recurring item moves it to its next date, so undoing that puts back the old one.
+pub fn uncomplete_item(config: &Config, id: &str) -> Result<(), Error> {
+    let due = cache::load(config)?
+        .closed
+        .into_iter()
+        .find(|item| item.id == id)
+        .and_then(|item| item.due);
+    cache::restore_item(config, id)?;
+    if !outbox::cancel_close(config, id)? {
+        let command = match due {
+            Some(due) if due.is_recurring => {
+                let args = json!({ "id": id, "due": { "date": due.date, "string": due.string } });
+                SyncCommand::new("item_update", args)
+            }
+            _ => SyncCommand::new("item_uncomplete", json!({ "id": id })),
+        };
+        send_command(config, command)?;
+    }
+
+    Ok(())
+}
+
+/// Add an item with Todoist parsing the text, i.e. "Call Bob tomorrow 3pm p1".
+/// It goes in the given project unless the text names another with #, or in the Inbox for views.
+pub fn quick_add(config: &Config, project_name: &str, text: &str) -> Result<(), Error> {
+    // Not sent through the outbox, adding the same text twice would add two items
+    let body = json!({ "text": text });
+    let json = post_todoist_sync(config, String::from(QUICK_ADD_URL), body)?;
+    let mut item: Item = serde_json::from_str(&json).map_err(|e| Error::ResponseParse {
+        what: String::from("quick add"),
+        cause: e.to_string(),
+    })?;
+
+    // Todoist left it in the Inbox when the text named no project, "#123" is not one
+    let is_project =
+        project_name != projects::ALL_PROJECTS && !config.filters.contains_key(project_name);
+    let inbox_id = cache::load(config).unwrap_or_default().inbox_id();
+    if is_project && inbox_id.as_ref() == Some(&item.project_id) {
+        let project_id = projects::project_id(config, project_name)?;
+        if item.project_id != project_id {
+            let args = json!({ "id": item.id, "project_id": project_id });
+            send_command(config, SyncCommand::new("item_move", args))?;
+            item.project_id = project_id;
+        }
+    }
+    cache::update_item(config, item)
+}
+
+/// Move the due date of an item, recurring items stay recurring
+pub fn update_due(config: &Config, id: &str, date: &str) -> Result<(), Error> {
+    let find = |cache: Cache| cache.items.into_iter().find(|item| item.id == id);
+    // Without the item it isn't known whether it recurs, and a bare date would end the series
+    let item = match find(cache::load(config)?) {
+        Some(item) => item,
+        None => find(cache::refresh(config)?).ok_or_else(|| Error::ItemNotFound(id.to_owned()))?,
+    };
+    let date = String::from(date);
+
+    let due = match item.due.clone() {
+        Some(DateInfo {
+            is_recurring: true,
+            string,
+            ..
+        }) => DateInfo {
+            date,
+            is_recurring: true,
+            timezone: None,
+            string,
+        },
+        _ => DateInfo {
+            date,
+            is_recurring: false,
+            timezone: None,
+            string: String::new(),
+        },
+    };
+
+    let args = match due.is_recurring {
+        true => json!({ "id": id, "due": { "date": due.date, "string": due.string } }),
+        false => json!({ "id": id, "due": { "date": due.date } }),
+    };
+    let due = Some(due);
+    cache::update_item(config, Item { due, ..item })?;
+    send_command(config, SyncCommand::new("item_update", args))
+}
+
+/// Queue a command in the outbox and try to send it, it stays queued if Todoist can't be reached
+fn send_command(config: &Config, command: SyncCommand) -> Result<(), Error> {
+    let uuid = command.uuid.clone();
+    outbox::push(config, command)?;
+
+    match outbox::flush_command(config, &uuid) {
+        Err(e) if !e.is_transient() => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Send commands in one batch, Todoist applies them in order
+pub fn sync_commands(config: &Config, commands: &[SyncCommand]) -> Result<SyncResponse, Error> {
+    let body = json!({ "commands": commands });
+    sync(config, body)
 }
 
-/// Complete the last item returned by "next item"
-pub fn complete_item(config: Config) -> Result<String, String> {
-    let body = json!({"commands": [{"type": "item_close", "uuid": new_uuid(), "temp_id": new_uuid(), "args": {"id": config.next_id}}]});
+fn sync(config: &Config, body: serde_json::Value) -> Result<SyncResponse, Error> {
     let url = String::from(SYNC_URL);
+    let json = post_todoist_sync(config, url, body)?;
 
-    post_todoist_sync(config.token.clone(), url, body)?;
+    serde_json::from_str(&json).map_err(|e| Error::ResponseParse {
+        what: String::from("sync"),
+        cause: e.to_string(),
+    })
+}
 
-    if !cfg!(test) {
-        config.clear_next_id().save()?;
+/// The ids of the open items that match a Todoist filter query, i.e. "today & p1"
+pub fn filter_item_ids(config: &Config, query: &str) -> Result<Vec<String>, Error> {
+    #[derive(Deserialize)]
+    struct Task {
+        id: String,
     }
 
-    // Does not pass back an item
-    Ok(String::from("✓"))
+    let json = get_todoist_rest(config, String::from(TASKS_URL), &[("filter", query)])?;
+    let tasks: Vec<Task> = serde_json::from_str(&json).map_err(|e| Error::ResponseParse {
+        what: format!("filter {query}"),
+        cause: e.to_string(),
+    })?;
+
+    Ok(tasks.into_iter().map(|task| task.id).collect())
 }
 
-/// Post to Todoist via sync API
+/// Post to Todoist via sync API, retrying connection errors, 5xx and 429 responses
 fn post_todoist_sync(
-    token: String,
+    config: &Config,
     url: String,
     body: serde_json::Value,
-) -> Result<String, String> {
-    let todoist_url: &str = "https://api.todoist.com";
+) -> Result<String, Error> {
+    with_retries(config, || post_once(config, &url, &body))
+}
+
+/// Get from Todoist via REST API, retrying like post_todoist_sync
+fn get_todoist_rest(config: &Config, url: String, query: &[(&str, &str)]) -> Result<String, Error> {
+    with_retries(config, || get_once(config, &url, query))
+}
+
+fn with_retries(
+    config: &Config,
+    send: impl Fn() -> Result<String, Error>,
+) -> Result<String, Error> {
+    let mut attempt = 0;
+    loop {
+        match send() {
+            Err(error) if attempt < config.network.max_retries => {
+                match retry_delay(&config.network, attempt, &error) {
+                    Some(delay) => {
+                        thread::sleep(delay);
+                        attempt += 1;
+                    }
+                    None => return Err(error),
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+fn post_once(config: &Config, url: &str, body: &serde_json::Value) -> Result<String, Error> {
+    let todoist_url = todoist_url(config);
+    let token = &config.token;
 
     let request_url = format!("{todoist_url}{url}");
 
-    let response = Client::new()
+    let response = client(&config.network)?
         .post(request_url)
         .header(CONTENT_TYPE, "application/json")
         .header(AUTHORIZATION, format!("Bearer {token}"))
-        .json(&body)
-        .send()
-        .or(Err("Did not get response from server"))?;
+        .json(body)
+        .send()?;
+
+    read_response(response)
+}
+
+fn get_once(config: &Config, url: &str, query: &[(&str, &str)]) -> Result<String, Error> {
+    let todoist_url = todoist_url(config);
+    let token = &config.token;
+
+    let request_url = format!("{todoist_url}{url}");
+
+    let response = client(&config.network)?
+        .get(request_url)
+        .query(query)
+        .header(AUTHORIZATION, format!("Bearer {token}"))
+        .send()?;
+
+    read_response(response)
+}
 
-    if response.status().is_success() {
-        Ok(response.text().or(Err("Could not read response text"))?)
+fn read_response(response: reqwest::blocking::Response) -> Result<String, Error> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response.text()?)
     } else {
-        Err(format!("Error: {:#?}", response.text()))
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        let body = response.text().unwrap_or_default();
+
+        Err(Error::from_status(status.as_u16(), retry_after, body))
+    }
+}
+
+/// Retry-After is either a number of seconds or an HTTP-date, i.e. "Wed, 21 Oct 2015 07:28:00 GMT"
+fn parse_retry_after(value: &str) -> Option<u64> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(seconds);
     }
+    let date = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    let seconds = (date.with_timezone(&Utc) - Utc::now()).num_seconds();
+
+    Some(seconds.max(0) as u64)
+}
+
+/// How long to wait before retrying, or None if the error is not worth retrying
+fn retry_delay(network: &Network, attempt: u32, error: &Error) -> Option<Duration> {
+    match error {
+        Error::RateLimit {
+            retry_after: Some(seconds),
+        } if *seconds > MAX_RETRY_AFTER => None,
+        Error::RateLimit {
+            retry_after: Some(seconds),
+        } => Some(Duration::from_secs(*seconds)),
+        error if error.is_transient() => Some(backoff(network.retry_delay, attempt)),
+        _ => None,
+    }
+}
+
+/// Exponential backoff with jitter, somewhere between half and all of retry_delay * 2^attempt
+fn backoff(retry_delay: u64, attempt: u32) -> Duration {
+    let millis = retry_delay.saturating_mul(2_u64.saturating_pow(attempt));
+    let jitter = rand::thread_rng().gen_range(0..=millis / 2);
+
+    Duration::from_millis(millis - jitter)
+}
+
+fn client(network: &Network) -> Result<Client, Error> {
+    let timeouts = (network.connect_timeout, network.timeout);
+    let mut client = CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    match &*client {
+        Some((built_with, client)) if *built_with == timeouts => Ok(client.clone()),
+        _ => {
+            let new_client = Client::builder()
+                .connect_timeout(Duration::from_secs(network.connect_timeout))
+                .timeout(Duration::from_secs(network.timeout))
+                .build()?;
+            *client = Some((timeouts, new_client.clone()));
+            Ok(new_client)
+        }
+    }
+}
+
+/// The base URL for API requests, the environment variable takes precedence over config
+fn todoist_url(config: &Config) -> String {
+    // Tests point config at their own stub server
+    std::env::var(TODOIST_URL_ENV)
+        .ok()
+        .filter(|_| !cfg!(test))
+        .or_else(|| config.todoist_url.clone())
+        .unwrap_or_else(|| String::from(TODOIST_URL))
+        .trim_end_matches('/')
+        .to_owned()
 }
 
 /// Create a new UUID, required for Todoist API
@@ -70,3 +373,274 @@ fn new_uuid() -> String {
         Uuid::new_v4().to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub_server::{self, Reply, StubServer};
+    use crate::test;
+    use crate::time::{self, Snooze};
+    use pretty_assertions::assert_eq;
+    use std::time::Instant;
+
+    fn stub_config(server: &StubServer, max_retries: u32) -> Config {
+        let config = test::helpers::config_fixture();
+        Config {
+            todoist_url: Some(server.url.clone()),
+            network: Network {
+                max_retries,
+                ..config.network.clone()
+            },
+            ..config
+        }
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        server.script(vec![Reply::status(500), Reply::status(503)]);
+        let config = stub_config(&server, 3);
+
+        let response = sync_resources(&config, "*").unwrap();
+
+        assert_eq!(response.items.len(), 4);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn retries_connection_errors() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let config = Config {
+            todoist_url: Some(url),
+            ..test::helpers::config_fixture()
+        };
+
+        let result = sync_resources(&config, "*").map(|response| response.items);
+
+        assert!(matches!(result, Err(Error::Network(_))));
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        server.script(vec![Reply::status(502); 3]);
+        let config = stub_config(&server, 2);
+
+        let result = sync_resources(&config, "*").map(|response| response.items);
+
+        assert_eq!(
+            result,
+            Err(Error::Server {
+                status: 502,
+                body: String::new()
+            })
+        );
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn does_not_retry_auth_errors() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        server.script(vec![Reply::status(401)]);
+        let config = stub_config(&server, 3);
+
+        let result = sync_resources(&config, "*").map(|response| response.items);
+
+        assert_eq!(result, Err(Error::Auth { status: 401 }));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn honours_retry_after() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        server.script(vec![Reply::status(429).header("Retry-After", "1")]);
+        let config = stub_config(&server, 3);
+        let start = Instant::now();
+
+        assert!(sync_resources(&config, "*").is_ok());
+        assert!(start.elapsed() >= Duration::from_secs(1));
+
+        server.script(vec![Reply::status(429).header("Retry-After", "3600")]);
+        let result = sync_resources(&config, "*").map(|response| response.items);
+        assert_eq!(
+            result,
+            Err(Error::RateLimit {
+                retry_after: Some(3600)
+            })
+        );
+    }
+
+    #[test]
+    fn retry_after_can_be_seconds_or_an_http_date() {
+        assert_eq!(parse_retry_after("120"), Some(120));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(0));
+        let later = (Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let seconds = parse_retry_after(&later).unwrap();
+        assert!((88..=90).contains(&seconds));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn sync_reports_unexpected_responses() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        server.script(vec![Reply {
+            body: String::from("2{.e"),
+            ..Reply::status(200)
+        }]);
+        let config = stub_config(&server, 0);
+
+        let error_text = String::from("Could not parse response for sync: invalid type: integer `2`, expected struct SyncResponse at line 1 column 1");
+        assert_eq!(
+            sync_resources(&config, "*").unwrap_err().to_string(),
+            error_text
+        );
+    }
+
+    #[test]
+    fn update_due_keeps_recurrence() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let recurring = Item {
+            id: String::from("6543210005"),
+            due: Some(DateInfo {
+                date: String::from("2022-01-01"),
+                is_recurring: true,
+                timezone: None,
+                string: String::from("every day"),
+            }),
+            ..test::helpers::item_fixture()
+        };
+        cache::update_item(&config, recurring).unwrap();
+        let tomorrow = Snooze::Tomorrow.due_string(time::now(&config));
+
+        update_due(&config, "6543210005", &tomorrow).unwrap();
+
+        let body: serde_json::Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+        let command = &body["commands"][0];
+        assert_eq!(command["type"], "item_update");
+        assert_eq!(command["args"]["id"], "6543210005");
+        assert_eq!(command["args"]["due"]["date"], tomorrow.as_str());
+        assert_eq!(command["args"]["due"]["string"], "every day");
+
+        let cached = cache::load(&config).unwrap().items;
+        assert_eq!(cached[0].due.as_ref().unwrap().date, tomorrow);
+    }
+
+    #[test]
+    fn update_due_fetches_items_missing_from_the_cache() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+
+        update_due(&config, "6543210002", "2023-02-01").unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let body: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+        let due = &body["commands"][0]["args"]["due"];
+        assert_eq!(due["date"], "2023-02-01");
+
+        assert_eq!(
+            update_due(&config, "missing", "2023-02-01"),
+            Err(Error::ItemNotFound(String::from("missing")))
+        );
+        assert_eq!(server.requests().len(), 3);
+        assert_eq!(outbox::load(&config).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn uncomplete_item_reopens_sent_and_cancels_queued_completions() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let item = Item {
+            id: String::from("6543210005"),
+            ..test::helpers::item_fixture()
+        };
+        cache::update_item(&config, item.clone()).unwrap();
+
+        complete_item(&config, "6543210005").unwrap();
+        assert_eq!(uncomplete_item(&config, "6543210005"), Ok(()));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].body.contains("\"item_uncomplete\""));
+        assert_eq!(cache::load(&config).unwrap().items, vec![item]);
+
+        // Still waiting in the outbox, so there is nothing to reopen
+        let command = SyncCommand::new("item_close", json!({ "id": "6543210005" }));
+        outbox::push(&config, command).unwrap();
+        uncomplete_item(&config, "6543210005").unwrap();
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(outbox::load(&config), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn uncomplete_item_puts_back_the_date_of_recurring_items() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let item = Item {
+            id: String::from("6543210005"),
+            due: Some(DateInfo {
+                date: String::from("2022-01-01"),
+                is_recurring: true,
+                timezone: None,
+                string: String::from("every day"),
+            }),
+            ..test::helpers::item_fixture()
+        };
+        cache::update_item(&config, item.clone()).unwrap();
+
+        complete_item(&config, "6543210005").unwrap();
+        assert_eq!(uncomplete_item(&config, "6543210005"), Ok(()));
+
+        let body: serde_json::Value = serde_json::from_str(&server.requests()[1].body).unwrap();
+        let command = &body["commands"][0];
+        assert_eq!(command["type"], "item_update");
+        assert_eq!(command["args"]["id"], "6543210005");
+        assert_eq!(command["args"]["due"]["date"], "2022-01-01");
+        assert_eq!(command["args"]["due"]["string"], "every day");
+        assert_eq!(cache::load(&config).unwrap().items, vec![item]);
+    }
+
+    #[test]
+    fn quick_add_moves_the_item_to_the_project() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        cache::refresh(&config).unwrap();
+
+        quick_add(&config, "Home", "Fix bug #123 tomorrow 3pm p1").unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[1].path, "/sync/v9/quick/add");
+        assert_eq!(
+            requests[1].body,
+            "{\"text\":\"Fix bug #123 tomorrow 3pm p1\"}"
+        );
+        assert!(requests[2].body.contains("\"item_move\""));
+        assert!(requests[2].body.contains("\"project_id\":\"2203306141\""));
+        let cached = cache::load(&config)
+            .unwrap()
+            .items_for_project("2203306141");
+        assert!(cached.iter().any(|item| item.id == "6543210009"));
+
+        // Left where Todoist put it when the text names a project
+        let fixture =
+            std::fs::read_to_string(stub_server::default_fixtures().join("quick_add.json"));
+        let body = fixture.unwrap().replace("2203306140", "2203306142");
+        server.script(vec![Reply {
+            body,
+            ..Reply::status(200)
+        }]);
+        quick_add(&config, "Home", "Call Bob #Work").unwrap();
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter() {
+        for attempt in 0..4 {
+            let ceiling = 100 * 2_u64.pow(attempt);
+            let delay = backoff(100, attempt).as_millis() as u64;
+            assert!(delay >= ceiling / 2 && delay <= ceiling);
+        }
+    }
+}
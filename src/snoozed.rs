@@ -0,0 +1,63 @@
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::store;
+use crate::time;
+
+const FILENAME: &str = "tot_snoozed.json";
+
+/// Items snoozed to a time that is still today, by id, with the time as "2023-01-31T20:00:00".
+/// Being due today they would be ranked first again straight away, so they are passed over
+/// until then.
+pub fn path(config: &Config) -> PathBuf {
+    store::path(config, FILENAME)
+}
+
+pub fn load(config: &Config) -> Result<HashMap<String, String>, Error> {
+    store::load(&path(config))
+}
+
+/// Pass over the item until the time, snoozes that are over are forgotten
+pub fn add(config: &Config, id: &str, until: &str) -> Result<(), Error> {
+    let mut snoozed = load(config)?;
+    snoozed.insert(id.to_owned(), until.to_owned());
+    snoozed.retain(|_, until| is_later(config, until));
+    store::save(&path(config), &snoozed)
+}
+
+/// The ids of items snoozed to a time that hasn't come yet
+pub fn ids(config: &Config) -> Result<Vec<String>, Error> {
+    Ok(load(config)?
+        .into_iter()
+        .filter(|(_, until)| is_later(config, until))
+        .map(|(id, _)| id)
+        .collect())
+}
+
+fn is_later(config: &Config, until: &str) -> bool {
+    NaiveDateTime::parse_from_str(until, "%Y-%m-%dT%H:%M:%S")
+        .is_ok_and(|until| until > time::now(config).naive_local())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test;
+    use chrono::Duration;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn snoozes_are_over_once_their_time_has_come() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let now = time::now(&config).naive_local();
+        let format = |datetime: NaiveDateTime| datetime.format("%Y-%m-%dT%H:%M:%S").to_string();
+
+        add(&config, "1", &format(now + Duration::hours(1))).unwrap();
+        add(&config, "2", &format(now - Duration::minutes(1))).unwrap();
+        assert_eq!(ids(&config), Ok(vec![String::from("1")]));
+        assert_eq!(load(&config).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,81 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::Config;
+use crate::error::Error;
+
+/// A file kept in the same directory as config
+pub fn path(config: &Config, filename: &str) -> PathBuf {
+    PathBuf::from(&config.path).with_file_name(filename)
+}
+
+/// Read JSON from path, or the default value if there is no file yet
+pub fn load<T: DeserializeOwned + Default>(path: &Path) -> Result<T, Error> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let path_string = path.display().to_string();
+    let json = fs::read_to_string(path).map_err(|e| Error::config_io(&path_string, e))?;
+    serde_json::from_str(&json).map_err(|e| Error::ConfigParse {
+        path: path_string,
+        cause: e.to_string(),
+    })
+}
+
+pub fn save<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let path_string = path.display().to_string();
+    let json = serde_json::to_string_pretty(value).map_err(|e| Error::ConfigParse {
+        path: path_string.clone(),
+        cause: e.to_string(),
+    })?;
+
+    fs::write(path, json).map_err(|e| Error::config_io(&path_string, e))
+}
+
+/// The lines of a text file, no file yet is the same as an empty one
+pub fn read_lines(path: &Path) -> Result<Vec<String>, Error> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text.lines().map(String::from).collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(Error::config_io(&path.display().to_string(), e)),
+    }
+}
+
+/// The paths in a directory, no directory yet is the same as an empty one
+pub fn read_dir(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    match fs::read_dir(path) {
+        Ok(entries) => Ok(entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(Error::config_io(&path.display().to_string(), e)),
+    }
+}
+
+/// The latest time any of the paths was changed, None when none of them exist
+pub fn modified(paths: &[PathBuf]) -> Option<SystemTime> {
+    paths
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok()?.modified().ok())
+        .max()
+}
+
+pub fn write_lines(path: &Path, lines: Vec<String>) -> Result<(), Error> {
+    let text: String = lines.into_iter().map(|line| line + "\n").collect();
+    fs::write(path, text).map_err(|e| Error::config_io(&path.display().to_string(), e))
+}
+
+/// A short FNV-1a hash of text that is the same on every run, for ids that point at a line
+/// and have to tell when the line now holds something else
+pub fn fingerprint(text: &str) -> String {
+    let hash = text.bytes().fold(0x811c9dc5_u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    format!("{hash:08x}")
+}
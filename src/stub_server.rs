@@ -0,0 +1,211 @@
+//! A local stand-in for the Todoist API that answers requests from fixture files.
+//!
+//! Used by the tests, and by `cargo run --example stub_server` for offline demos.
+//! A request to `/sync/v9/sync` is answered with `sync.json` from the fixtures directory,
+//! `/sync/v9/projects/get_data` with `projects_get_data.json` and so on.
+//! Scripted replies, i.e. failures, are sent first in the order they were added.
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A request received by the stub server
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Request {
+    pub path: String,
+    pub body: String,
+}
+
+/// A canned reply that takes precedence over the fixtures
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reply {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Reply {
+    pub fn status(status: u16) -> Reply {
+        Reply {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    pub fn header(self, name: &str, value: &str) -> Reply {
+        let mut headers = self.headers;
+        headers.push((name.to_owned(), value.to_owned()));
+        Reply { headers, ..self }
+    }
+}
+
+pub struct StubServer {
+    /// Base URL to use as `todoist_url` in config
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+    script: Arc<Mutex<VecDeque<Reply>>>,
+}
+
+impl StubServer {
+    /// Start a server on a random free port
+    pub fn start(fixtures: &Path) -> io::Result<StubServer> {
+        StubServer::bind("127.0.0.1:0", fixtures)
+    }
+
+    /// Start a server on the given address, requests are handled on background threads
+    pub fn bind(address: &str, fixtures: &Path) -> io::Result<StubServer> {
+        let listener = TcpListener::bind(address)?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let script = Arc::new(Mutex::new(VecDeque::new()));
+        let fixtures = fixtures.to_path_buf();
+        let log = requests.clone();
+        let replies = script.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let fixtures = fixtures.clone();
+                let log = log.clone();
+                let replies = replies.clone();
+                thread::spawn(move || handle(stream, &fixtures, &log, &replies));
+            }
+        });
+
+        Ok(StubServer {
+            url,
+            requests,
+            script,
+        })
+    }
+
+    /// Queue replies to send before falling back to the fixtures
+    pub fn script(&self, replies: Vec<Reply>) {
+        self.script.lock().unwrap().extend(replies);
+    }
+
+    /// All requests received so far, oldest first
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// The fixtures directory bundled with the crate
+pub fn default_fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+}
+
+fn handle(
+    stream: TcpStream,
+    fixtures: &Path,
+    log: &Mutex<Vec<Request>>,
+    script: &Mutex<VecDeque<Reply>>,
+) {
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err(_) => return,
+    };
+
+    let scripted = script.lock().unwrap().pop_front();
+    let reply = match (scripted, fixture_for(fixtures, &request.path)) {
+        (Some(reply), _) => reply,
+        (None, Some(body)) => Reply {
+            body,
+            ..Reply::status(200)
+        },
+        (None, None) => Reply {
+            body: String::from("Not Found"),
+            ..Reply::status(404)
+        },
+    };
+
+    log.lock().unwrap().push(request);
+    let _ = (&stream).write_all(response(&reply).as_bytes());
+}
+
+fn read_request(stream: &TcpStream) -> io::Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_owned();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Request {
+        path,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+/// Maps "/sync/v9/projects/get_data" to "projects_get_data.json"
+fn fixture_for(fixtures: &Path, path: &str) -> Option<String> {
+    let path = path.split('?').next().unwrap_or_default();
+    let name = path.trim_start_matches("/sync/v9/").trim_matches('/');
+    if name.is_empty() || name.contains("..") {
+        return None;
+    }
+
+    let filename = format!("{}.json", name.replace('/', "_"));
+    std::fs::read_to_string(fixtures.join(filename)).ok()
+}
+
+fn response(reply: &Reply) -> String {
+    let headers: String = reply
+        .headers
+        .iter()
+        .map(|(name, value)| format!("{name}: {value}\r\n"))
+        .collect();
+
+    format!(
+        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n{headers}\r\n{}",
+        reply.status,
+        reply.body.len(),
+        reply.body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_project_data_from_its_fixture() {
+        let server = StubServer::start(&default_fixtures()).unwrap();
+        let url = format!("{}/sync/v9/projects/get_data", server.url);
+
+        let response = reqwest::blocking::Client::new()
+            .post(url)
+            .body("{\"project_id\":\"2203306141\"}")
+            .send()
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert!(response.text().unwrap().contains("\"name\": \"Home\""));
+        assert_eq!(server.requests()[0].path, "/sync/v9/projects/get_data");
+    }
+}
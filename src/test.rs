@@ -1,13 +1,17 @@
 #[cfg(test)]
 pub mod helpers {
+    use crate::backend::{Backend, TaskBackend};
     use crate::config;
-    use crate::config::Config;
+    use crate::config::{Config, Network, Scoring, Subtasks};
     use crate::items::{DateInfo, Item};
+    use crate::stub_server::StubServer;
     use std::collections::HashMap;
+    use uuid::Uuid;
 
     pub fn item_fixture() -> Item {
         Item {
             id: String::from("222"),
+            project_id: String::from("2203306141"),
             content: String::from("Get gifts for the twins"),
             checked: false,
             description: String::from(""),
@@ -15,9 +19,15 @@ pub mod helpers {
                 date: String::from("2061-11-13"),
                 is_recurring: false,
                 timezone: Some(String::from("America/Los_Angeles")),
+                string: String::new(),
             }),
             priority: 3,
             is_deleted: false,
+            added_at: None,
+            labels: Vec::new(),
+            parent_id: None,
+            section_id: None,
+            child_order: 0,
         }
     }
 
@@ -29,6 +39,50 @@ pub mod helpers {
             next_id: None,
             last_version_check: None,
             timezone: Some(String::from("US/Pacific")),
+            todoist_url: None,
+            network: Network {
+                retry_delay: 1,
+                ..Network::default()
+            },
+            visible_projects: None,
+            ranking: HashMap::new(),
+            scoring: Scoring::default(),
+            up_next: 0,
+            exclude_labels: Vec::new(),
+            subtasks: Subtasks::default(),
+            filters: HashMap::new(),
+            backend: Backend::default(),
+            hidden_projects: Vec::new(),
         }
     }
+
+    /// Saves config in a new temporary directory, so files kept next to it don't collide
+    pub fn save_to_temp_dir(config: Config) -> Config {
+        let dir = std::env::temp_dir().join(format!("tot-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        Config {
+            path: dir.join("tod.cfg").to_str().unwrap().to_owned(),
+            ..config
+        }
+        .create()
+        .unwrap()
+    }
+
+    /// A config saved to a temporary directory that points at the stub server
+    pub fn config_with_stub(server: &StubServer) -> Config {
+        let projects = HashMap::from([(String::from("Home"), String::from("2203306141"))]);
+
+        save_to_temp_dir(Config {
+            projects,
+            todoist_url: Some(server.url.clone()),
+            ..config_fixture()
+        })
+    }
+
+    /// The ids of the open items in a view of a backend, in the order they were read
+    pub fn ids(backend: &dyn TaskBackend, config: &Config, view: &str) -> Vec<String> {
+        let items = backend.items(config, view).unwrap();
+        items.into_iter().map(|item| item.id).collect()
+    }
 }
@@ -1,10 +1,65 @@
 use crate::config::Config;
+use crate::error::Error;
 use chrono::offset::{TimeZone, Utc};
-use chrono::{DateTime, NaiveDate};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime};
 use chrono_tz::{Tz, TZ_VARIANTS};
 
+/// When to push the current task back to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Snooze {
+    OneHour,
+    Tonight,
+    Tomorrow,
+    NextWeek,
+}
+
+impl Snooze {
+    pub const ALL: [Snooze; 4] = [
+        Snooze::OneHour,
+        Snooze::Tonight,
+        Snooze::Tomorrow,
+        Snooze::NextWeek,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Snooze::OneHour => "1 hour",
+            Snooze::Tonight => "Tonight",
+            Snooze::Tomorrow => "Tomorrow",
+            Snooze::NextWeek => "Next week",
+        }
+    }
+
+    /// The new due date in Todoist's format, a datetime for hours and a date for days
+    pub fn due_string(&self, now: DateTime<Tz>) -> String {
+        let tonight = now
+            .date_naive()
+            .and_time(NaiveTime::from_hms_opt(20, 0, 0).unwrap());
+        match self {
+            Snooze::OneHour => (now.naive_local() + Duration::hours(1))
+                .format("%Y-%m-%dT%H:%M:00")
+                .to_string(),
+            // Tomorrow night if it is already past 8pm
+            Snooze::Tonight if now.naive_local() >= tonight => (tonight + Duration::days(1))
+                .format("%Y-%m-%dT%H:%M:%S")
+                .to_string(),
+            Snooze::Tonight => tonight.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            Snooze::Tomorrow => (now.date_naive() + Duration::days(1))
+                .format("%Y-%m-%d")
+                .to_string(),
+            Snooze::NextWeek => {
+                let days_until_monday = 7 - now.weekday().num_days_from_monday() as i64;
+                (now.date_naive() + Duration::days(days_until_monday))
+                    .format("%Y-%m-%d")
+                    .to_string()
+            }
+        }
+    }
+}
+
 pub fn now(config: &Config) -> DateTime<Tz> {
-    let tz = timezone_from_str(&config.timezone);
+    // The timezone is validated when config is loaded
+    let tz = timezone_from_str(&config.timezone).unwrap_or(Tz::UTC);
     Utc::now().with_timezone(&tz)
 }
 
@@ -39,7 +94,7 @@ pub fn format_date(date: &NaiveDate, config: &Config) -> String {
 }
 
 pub fn format_datetime(datetime: &DateTime<Tz>, config: &Config) -> String {
-    let tz = timezone_from_str(&config.timezone);
+    let tz = timezone_from_str(&config.timezone).unwrap_or(Tz::UTC);
     if datetime_is_today(*datetime, config) {
         datetime.with_timezone(&tz).format("%H:%M").to_string()
     } else {
@@ -48,47 +103,54 @@ pub fn format_datetime(datetime: &DateTime<Tz>, config: &Config) -> String {
 }
 
 /// Parse DateTime
-pub fn datetime_from_str(str: &str, timezone: Tz) -> Result<DateTime<Tz>, String> {
+pub fn datetime_from_str(str: &str, timezone: Tz) -> Result<DateTime<Tz>, Error> {
     let datetime = match str.len() {
         19 => timezone
             .datetime_from_str(str, "%Y-%m-%dT%H:%M:%S")
-            .expect("could not parse DateTime"),
+            .map_err(|e| Error::date_parse(str, e))?,
         20 => Utc
             .datetime_from_str(str, "%Y-%m-%dT%H:%M:%SZ")
-            .expect("could not parse DateTime")
+            .map_err(|e| Error::date_parse(str, e))?
             .with_timezone(&Tz::UTC),
-        _ => return Err(format!("cannot parse DateTime: {str}")),
+        _ => return Err(Error::date_parse(str, "unknown DateTime format")),
     };
 
     Ok(datetime)
 }
 
-pub fn timezone_from_str(timezone_string: &Option<String>) -> Tz {
+pub fn timezone_from_str(timezone_string: &Option<String>) -> Result<Tz, Error> {
     match timezone_string {
-        None => Tz::UTC,
-        Some(string) => string.parse::<Tz>().unwrap(),
+        None => Ok(Tz::UTC),
+        Some(string) => string
+            .parse::<Tz>()
+            .map_err(|e| Error::date_parse(string, e)),
     }
 }
 
 /// Parse Date
-pub fn date_from_str(str: &str, timezone: Tz) -> Result<NaiveDate, String> {
+pub fn date_from_str(str: &str, timezone: Tz) -> Result<NaiveDate, Error> {
     let date = match str.len() {
-        10 => NaiveDate::parse_from_str(str, "%Y-%m-%d").or(Err("could not parse Date"))?,
+        10 => NaiveDate::parse_from_str(str, "%Y-%m-%d").map_err(|e| Error::date_parse(str, e))?,
         19 => timezone
             .datetime_from_str(str, "%Y-%m-%dT%H:%M:%S")
-            .or(Err("could not parse DateTime"))?
+            .map_err(|e| Error::date_parse(str, e))?
             .date_naive(),
 
         20 => timezone
             .datetime_from_str(str, "%Y-%m-%dT%H:%M:%SZ")
-            .or(Err("could not parse DateTime"))?
+            .map_err(|e| Error::date_parse(str, e))?
             .date_naive(),
-        _ => return Err(format!("cannot parse NaiveDate, unknown length: {str}")),
+        _ => return Err(Error::date_parse(str, "unknown Date format")),
     };
 
     Ok(date)
 }
 
+/// Whether text is a date or datetime in one of the formats above
+pub fn is_date(text: &str) -> bool {
+    date_from_str(text, Tz::UTC).is_ok()
+}
+
 pub fn list_timezones() {
     println!("Timezones:");
     for (num, tz) in TZ_VARIANTS.iter().enumerate() {
@@ -99,3 +161,46 @@ pub fn list_timezones() {
 pub fn get_timezone(num: usize) -> String {
     TZ_VARIANTS[num].to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn timezone_from_str_works() {
+        assert_eq!(timezone_from_str(&None), Ok(Tz::UTC));
+        assert_eq!(
+            timezone_from_str(&Some(String::from("America/Vancouver"))),
+            Ok(Tz::America__Vancouver)
+        );
+        assert!(timezone_from_str(&Some(String::from("Mars/Olympus_Mons"))).is_err());
+    }
+
+    #[test]
+    fn snooze_due_string_works() {
+        // A Wednesday afternoon
+        let now = Tz::America__Vancouver
+            .with_ymd_and_hms(2023, 4, 12, 15, 30, 0)
+            .unwrap();
+
+        assert_eq!(Snooze::OneHour.due_string(now), "2023-04-12T16:30:00");
+        assert_eq!(Snooze::Tonight.due_string(now), "2023-04-12T20:00:00");
+        assert_eq!(Snooze::Tomorrow.due_string(now), "2023-04-13");
+        assert_eq!(Snooze::NextWeek.due_string(now), "2023-04-17");
+
+        let late = Tz::America__Vancouver
+            .with_ymd_and_hms(2023, 4, 16, 23, 30, 0)
+            .unwrap();
+        assert_eq!(Snooze::OneHour.due_string(late), "2023-04-17T00:30:00");
+        assert_eq!(Snooze::Tonight.due_string(late), "2023-04-17T20:00:00");
+        assert_eq!(Snooze::NextWeek.due_string(late), "2023-04-17");
+    }
+
+    #[test]
+    fn datetime_from_str_does_not_panic_on_bad_input() {
+        assert!(datetime_from_str("2021-02-27T19:41:5x", Tz::UTC).is_err());
+        assert!(datetime_from_str("2021-02-27T19:41:5xZ", Tz::UTC).is_err());
+        assert!(datetime_from_str("tomorrow", Tz::UTC).is_err());
+    }
+}
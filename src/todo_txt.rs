@@ -0,0 +1,346 @@
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::backend::TaskBackend;
+use crate::config::Config;
+use crate::error::Error;
+use crate::items::{DateInfo, Item};
+use crate::projects::ALL_PROJECTS;
+use crate::{store, time};
+
+/// Items in a todo.txt file, see https://github.com/todotxt/todo.txt.
+/// Ids are the line number like todo.sh uses and a hash of the item's text, i.e. "3:5f0c2a1e",
+/// so a line that moved because the file was edited elsewhere isn't taken for another item.
+/// Completed lines are kept so that line numbers don't change.
+pub struct TodoTxt {
+    pub path: String,
+}
+
+impl TaskBackend for TodoTxt {
+    /// Nothing to fetch, todo.txt is read again for every request
+    fn refresh(&self, _config: &Config, _view: Option<&str>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Every +project in the file, including those of completed items
+    fn views(&self, _config: &Config) -> Result<Vec<String>, Error> {
+        let mut names: Vec<String> = Vec::new();
+        for item in self.read_items()? {
+            if !item.project_id.is_empty() && !names.contains(&item.project_id) {
+                names.push(item.project_id);
+            }
+        }
+
+        names.sort();
+        Ok(names)
+    }
+
+    /// Every open item for "All projects", including those without a +project but not
+    /// those of hidden projects
+    fn items(&self, config: &Config, view: &str) -> Result<Vec<Item>, Error> {
+        let in_view = |item: &Item| match view {
+            ALL_PROJECTS => !config.hidden_projects.contains(&item.project_id),
+            _ => item.project_id == view,
+        };
+        Ok(self
+            .read_items()?
+            .into_iter()
+            .filter(|item| !item.checked && in_view(item))
+            .collect())
+    }
+
+    fn modified(&self, _config: &Config) -> Option<SystemTime> {
+        store::modified(&[PathBuf::from(&self.path)])
+    }
+
+    fn complete(&self, config: &Config, id: &str) -> Result<(), Error> {
+        let today = time::today_string(config);
+        self.update_line(id, |line| match line.starts_with("x ") {
+            true => line.to_owned(),
+            false => format!("x {today} {line}"),
+        })
+    }
+
+    fn uncomplete(&self, _config: &Config, id: &str) -> Result<(), Error> {
+        self.update_line(id, |line| match line.strip_prefix("x ") {
+            Some(rest) => strip_date(rest).1.to_owned(),
+            None => line.to_owned(),
+        })
+    }
+
+    fn update_due(&self, _config: &Config, id: &str, date: &str) -> Result<(), Error> {
+        self.update_line(id, |line| {
+            let due = format!("due:{date}");
+            let mut words: Vec<&str> = line.split(' ').collect();
+            match words.iter().position(|word| word.starts_with("due:")) {
+                Some(index) => words[index] = &due,
+                None => words.push(&due),
+            }
+            words.join(" ")
+        })
+    }
+
+    /// Appended with today as the creation date, in the view's project unless it names one
+    fn add(&self, config: &Config, view: &str, text: &str) -> Result<(), Error> {
+        let (priority, text) = match strip_priority(text.trim()) {
+            (Some(letter), rest) => (format!("({letter}) "), rest),
+            (None, rest) => (String::new(), rest),
+        };
+        let today = time::today_string(config);
+        let mut line = format!("{priority}{today} {text}");
+        if view != ALL_PROJECTS && !text.split(' ').any(|word| word.starts_with('+')) {
+            line = format!("{line} +{view}");
+        }
+
+        let mut lines = self.read_lines()?;
+        lines.push(line);
+        self.write_lines(lines)
+    }
+
+    fn add_example(&self) -> &'static str {
+        "(A) Call Bob +Home @phone due:2023-01-31"
+    }
+
+    /// Adds the +project in "All projects", where items from every project are ranked together
+    fn fmt_item(&self, config: &Config, item: &Item, view: &str) -> Result<String, Error> {
+        let text = item.fmt(config, None, None);
+        match view == ALL_PROJECTS && !item.project_id.is_empty() {
+            true => Ok(format!("{text}\nProject: {}", item.project_id)),
+            false => Ok(text),
+        }
+    }
+}
+
+impl TodoTxt {
+    fn read_lines(&self) -> Result<Vec<String>, Error> {
+        store::read_lines(Path::new(&self.path))
+    }
+
+    fn write_lines(&self, lines: Vec<String>) -> Result<(), Error> {
+        store::write_lines(Path::new(&self.path), lines)
+    }
+
+    fn read_items(&self) -> Result<Vec<Item>, Error> {
+        Ok(self
+            .read_lines()?
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| parse_line(index + 1, line))
+            .collect())
+    }
+
+    /// Change the line the id points at, as long as it still holds the same item
+    fn update_line(&self, id: &str, update: impl FnOnce(&str) -> String) -> Result<(), Error> {
+        let not_found = || Error::ItemNotFound(id.to_owned());
+        let (number, _) = id.split_once(':').ok_or_else(not_found)?;
+        let number = number.parse::<usize>().map_err(|_| not_found())?;
+        let mut lines = self.read_lines()?;
+        let line = number
+            .checked_sub(1)
+            .and_then(|index| lines.get_mut(index))
+            .filter(|line| parse_line(number, line).is_some_and(|item| item.id == id))
+            .ok_or_else(not_found)?;
+        *line = update(line);
+        self.write_lines(lines)
+    }
+}
+
+/// An item from a line of todo.txt, None for blank lines.
+/// Priorities (A) to (D) are Todoist's 4 to 1, the first +project is the project.
+pub fn parse_line(number: usize, line: &str) -> Option<Item> {
+    let mut rest = line.trim();
+    if rest.is_empty() {
+        return None;
+    }
+
+    let checked = rest.starts_with("x ");
+    if checked {
+        // The completion date
+        rest = strip_date(&rest[2..]).1;
+    }
+    let (letter, rest) = strip_priority(rest);
+    let (added_at, rest) = strip_date(rest);
+
+    let mut project_id = String::new();
+    let mut labels = Vec::new();
+    let mut due = None;
+    let mut content = Vec::new();
+    for word in rest.split_whitespace() {
+        match word {
+            _ if word.len() > 1 && word.starts_with('+') => {
+                if project_id.is_empty() {
+                    project_id = word[1..].to_owned();
+                }
+            }
+            _ if word.len() > 1 && word.starts_with('@') => labels.push(word[1..].to_owned()),
+            _ if word.starts_with("due:") && time::is_date(&word[4..]) => {
+                due = Some(DateInfo {
+                    date: word[4..].to_owned(),
+                    is_recurring: false,
+                    timezone: None,
+                    string: String::new(),
+                })
+            }
+            _ => content.push(word),
+        }
+    }
+
+    let priority = match letter {
+        Some('A') => 4,
+        Some('B') => 3,
+        Some('C') => 2,
+        _ => 1,
+    };
+
+    // Completing or moving the item leaves its text alone
+    let content = content.join(" ");
+    Some(Item {
+        id: format!("{number}:{}", store::fingerprint(&content)),
+        project_id,
+        content,
+        priority,
+        checked,
+        description: String::new(),
+        due,
+        is_deleted: false,
+        added_at: added_at.map(String::from),
+        labels,
+        parent_id: None,
+        section_id: None,
+        child_order: 0,
+    })
+}
+
+/// A leading "(A) " priority and the rest of the line
+fn strip_priority(line: &str) -> (Option<char>, &str) {
+    let bytes = line.as_bytes();
+    match bytes {
+        [b'(', letter, b')', b' ', ..] if letter.is_ascii_uppercase() => {
+            (Some(*letter as char), &line[4..])
+        }
+        _ => (None, line),
+    }
+}
+
+/// A leading "2023-01-31 " date and the rest of the line
+fn strip_date(line: &str) -> (Option<&str>, &str) {
+    match line.split_once(' ') {
+        Some((date, rest)) if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok() => {
+            (Some(date), rest)
+        }
+        _ => (None, line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test;
+    use crate::test::helpers::ids;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    fn todo_txt(config: &Config, text: &str) -> TodoTxt {
+        let path = store::path(config, "todo.txt").display().to_string();
+        fs::write(&path, text).unwrap();
+        TodoTxt { path }
+    }
+
+    #[test]
+    fn parse_line_reads_priority_dates_projects_and_contexts() {
+        let item = parse_line(3, "(B) 2023-01-02 Call Bob +Home @phone due:2023-01-05").unwrap();
+        assert_eq!(item.id, format!("3:{}", store::fingerprint("Call Bob")));
+        assert_eq!(item.priority, 3);
+        assert_eq!(item.added_at, Some(String::from("2023-01-02")));
+        assert_eq!(item.content, "Call Bob");
+        assert_eq!(item.project_id, "Home");
+        assert_eq!(item.labels, vec![String::from("phone")]);
+        assert_eq!(
+            item.due.map(|due| due.date),
+            Some(String::from("2023-01-05"))
+        );
+        assert!(!item.checked);
+
+        let done = parse_line(1, "x 2023-01-03 2023-01-02 Pay rent +Home").unwrap();
+        assert!(done.checked);
+        assert_eq!(done.priority, 1);
+        assert_eq!(done.added_at, Some(String::from("2023-01-02")));
+        assert_eq!(parse_line(2, "  "), None);
+    }
+
+    #[test]
+    fn completing_keeps_the_line_with_the_completion_date() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let backend = todo_txt(&config, "(A) Water the plants +Home\nPay rent +Home\n");
+        let today = time::today_string(&config);
+        let id = ids(&backend, &config, "Home")[0].clone();
+
+        backend.complete(&config, &id).unwrap();
+        assert_eq!(
+            fs::read_to_string(&backend.path).unwrap(),
+            format!("x {today} (A) Water the plants +Home\nPay rent +Home\n")
+        );
+        // Still a project while its only items are done
+        assert_eq!(backend.views(&config), Ok(vec![String::from("Home")]));
+        assert_eq!(ids(&backend, &config, "Home").len(), 1);
+
+        backend.uncomplete(&config, &id).unwrap();
+        assert_eq!(
+            fs::read_to_string(&backend.path).unwrap(),
+            "(A) Water the plants +Home\nPay rent +Home\n"
+        );
+    }
+
+    #[test]
+    fn update_due_replaces_the_due_tag() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let backend = todo_txt(&config, "Pay rent due:2023-01-05 +Home\nCall Bob +Home\n");
+
+        for id in ids(&backend, &config, "Home") {
+            backend.update_due(&config, &id, "2023-02-01").unwrap();
+        }
+        assert_eq!(
+            fs::read_to_string(&backend.path).unwrap(),
+            "Pay rent due:2023-02-01 +Home\nCall Bob +Home due:2023-02-01\n"
+        );
+    }
+
+    #[test]
+    fn added_items_get_today_and_the_project_of_the_view() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let backend = todo_txt(&config, "");
+        let today = time::today_string(&config);
+
+        backend
+            .add(&config, "Work", "(C) Review the report")
+            .unwrap();
+        backend.add(&config, "Work", "Call Bob +Home").unwrap();
+        backend.add(&config, ALL_PROJECTS, "Buy milk").unwrap();
+        assert_eq!(
+            fs::read_to_string(&backend.path).unwrap(),
+            format!(
+                "(C) {today} Review the report +Work\n{today} Call Bob +Home\n{today} Buy milk\n"
+            )
+        );
+        assert_eq!(backend.items(&config, ALL_PROJECTS).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn ids_stop_matching_when_lines_move() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let backend = todo_txt(&config, "Water the plants +Home\n");
+        let id = backend.items(&config, "Home").unwrap()[0].id.clone();
+
+        // Edited in another app while the item was shown
+        fs::write(&backend.path, "Call Bob\nWater the plants +Home\n").unwrap();
+        assert_eq!(
+            backend.complete(&config, &id),
+            Err(Error::ItemNotFound(id.clone()))
+        );
+        assert_eq!(
+            fs::read_to_string(&backend.path).unwrap(),
+            "Call Bob\nWater the plants +Home\n"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::backend::TaskBackend;
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::error::Error;
+use crate::items::Item;
+use crate::projects::{self, ALL_PROJECTS};
+use crate::{cache, outbox, request};
+
+/// Items from a Todoist account, cached and synced incrementally, changes go through the outbox
+pub struct Todoist;
+
+impl TaskBackend for Todoist {
+    fn refresh(&self, config: &Config, view: Option<&str>) -> Result<(), Error> {
+        cache::refresh(config)?;
+        if let Some((name, query)) = view.and_then(|view| config.filters.get_key_value(view)) {
+            let ids = request::filter_item_ids(config, query)?;
+            cache::set_filter(config, name, ids)?;
+        }
+        Ok(())
+    }
+
+    /// Every project name in tod's config or the Todoist account, and filter name in config
+    fn views(&self, config: &Config) -> Result<Vec<String>, Error> {
+        let mut names: Vec<String> = config.projects.keys().cloned().collect();
+        for name in config.filters.keys() {
+            if !names.contains(name) {
+                names.push(name.to_owned());
+            }
+        }
+        for project in cache::load(config)?.projects {
+            if !names.contains(&project.name) {
+                names.push(project.name);
+            }
+        }
+
+        names.sort();
+        Ok(names)
+    }
+
+    fn items(&self, config: &Config, view: &str) -> Result<Vec<Item>, Error> {
+        let cache = cache::load(config)?;
+        let items = match view {
+            ALL_PROJECTS => {
+                let project_ids = projects::visible_names(config)?
+                    .iter()
+                    .filter(|name| !config.filters.contains_key(*name))
+                    .filter(|name| !config.hidden_projects.contains(name))
+                    .map(|name| projects::project_id(config, name))
+                    .collect::<Result<Vec<String>, Error>>()?;
+                project_ids
+                    .iter()
+                    .flat_map(|project_id| cache.items_for_project(project_id))
+                    .collect()
+            }
+            name if config.filters.contains_key(name) => cache.items_for_filter(name),
+            _ => cache.items_for_project(&projects::project_id(config, view)?),
+        };
+        let pending_closes = outbox::pending_closes(config)?;
+
+        Ok(items
+            .into_iter()
+            .filter(|item| !pending_closes.contains(&item.id))
+            .collect())
+    }
+
+    fn has_items(&self, config: &Config) -> bool {
+        cache::load(config).is_ok_and(|cache| cache.is_synced())
+    }
+
+    fn complete(&self, config: &Config, id: &str) -> Result<(), Error> {
+        request::complete_item(config, id)
+    }
+
+    fn uncomplete(&self, config: &Config, id: &str) -> Result<(), Error> {
+        request::uncomplete_item(config, id)
+    }
+
+    fn update_due(&self, config: &Config, id: &str, date: &str) -> Result<(), Error> {
+        request::update_due(config, id, date)
+    }
+
+    fn add(&self, config: &Config, view: &str, text: &str) -> Result<(), Error> {
+        request::quick_add(config, view, text)
+    }
+
+    /// Todoist's quick add reads dates, times and priorities from the text
+    fn add_example(&self) -> &'static str {
+        "Call Bob tomorrow 3pm p1"
+    }
+
+    /// Adds the parent and section from the cache
+    fn fmt_item(&self, config: &Config, item: &Item, view: &str) -> Result<String, Error> {
+        let cache = cache::load(config)?;
+        let parent = cache
+            .items
+            .iter()
+            .find(|parent| Some(&parent.id) == item.parent_id.as_ref())
+            .map(|parent| parent.content.as_str());
+        let section = cache
+            .sections
+            .iter()
+            .find(|section| Some(&section.id) == item.section_id.as_ref())
+            .map(|section| section.name.as_str());
+
+        let text = item.fmt(config, parent, section);
+
+        // The project is only worth showing when items come from more than one
+        if view != ALL_PROJECTS && !config.filters.contains_key(view) {
+            return Ok(text);
+        }
+        match project_name(config, &cache, &item.project_id) {
+            Some(name) => Ok(format!("{text}\nProject: {name}")),
+            None => Ok(text),
+        }
+    }
+}
+
+/// The name of a project in tod's config or from the last sync
+fn project_name(config: &Config, cache: &Cache, project_id: &str) -> Option<String> {
+    config
+        .projects
+        .iter()
+        .find(|(_, id)| *id == project_id)
+        .map(|(name, _)| name.to_owned())
+        .or_else(|| {
+            cache
+                .projects
+                .iter()
+                .find(|project| project.id == project_id)
+                .map(|project| project.name.clone())
+        })
+}
@@ -0,0 +1,594 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::backend::{self, Backend};
+use crate::config;
+use crate::error::Error;
+use crate::outbox;
+use crate::projects;
+use crate::projects::Candidate;
+use crate::time::Snooze;
+
+/// How often to try sending the outbox when there is nothing else to do
+const REPLAY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often to check whether the backend's source was changed elsewhere
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How many items to explain the ranking of, including the one shown
+const CANDIDATES: usize = 4;
+
+/// Work for the background thread, so that the GUI never waits on the network.
+/// Items with an id in skipped are passed over when fetching the next item.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    /// Fetch the next item for a project
+    Next {
+        project: String,
+        skipped: Vec<String>,
+    },
+    /// Complete the current item and fetch the next one
+    Complete {
+        project: String,
+        skipped: Vec<String>,
+    },
+    /// Move the current item's due date and fetch the next one
+    Snooze {
+        project: String,
+        snooze: Snooze,
+        skipped: Vec<String>,
+    },
+    /// Add an item to the project with Todoist's quick add, then fetch the next one
+    Add {
+        project: String,
+        text: String,
+        skipped: Vec<String>,
+    },
+    /// Reopen a completed item and fetch the next one
+    Undo {
+        project: String,
+        id: String,
+        skipped: Vec<String>,
+    },
+    /// Show a particular item instead of the best ranked one
+    Promote {
+        project: String,
+        id: String,
+        skipped: Vec<String>,
+    },
+    /// Pass over the current item without changing it and fetch the runner-up
+    Skip {
+        project: String,
+        skipped: Vec<String>,
+    },
+    /// Sync with Todoist without fetching an item, i.e. to find projects
+    Refresh,
+    /// Save the projects to show in the bottom bar
+    SelectProjects(Vec<String>),
+    /// Leave a project out of "All projects" until the app is restarted
+    HideProject(String),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Response {
+    /// The outcome of a command, tagged with the project it was for
+    Next {
+        project: String,
+        result: Result<Option<String>, Error>,
+    },
+    /// The item shown for the project and the runners-up, with their scores
+    Candidates {
+        project: String,
+        candidates: Vec<Candidate>,
+        /// How many of the runners-up to list beneath the item shown
+        up_next: usize,
+    },
+    /// The id of the item that was closed by Complete, so that it can be undone
+    Completed { project: String, id: String },
+    /// The id of the item that was passed over by Skip
+    Skipped(String),
+    /// How many commands are waiting in the outbox
+    Pending(usize),
+    /// Every known project, and those picked for the bottom bar
+    Projects {
+        all: Vec<String>,
+        visible: Vec<String>,
+    },
+    /// The backend's source was changed elsewhere, i.e. a todo.txt file was edited
+    Changed,
+    /// A command that does not fetch an item went wrong
+    Failed(Error),
+}
+
+pub struct Worker {
+    commands: mpsc::Sender<Command>,
+    responses: mpsc::Receiver<Response>,
+}
+
+impl Worker {
+    /// Start the background thread, config is loaded from config_path or the default location
+    pub fn spawn(config_path: Option<String>) -> Worker {
+        let (commands, command_rx) = mpsc::channel();
+        let (response_tx, responses) = mpsc::channel();
+
+        thread::spawn(move || run(config_path, command_rx, response_tx));
+
+        Worker {
+            commands,
+            responses,
+        }
+    }
+
+    pub fn send(&self, command: Command) {
+        // The thread only stops once the Worker is dropped
+        let _ = self.commands.send(command);
+    }
+
+    /// Returns a response if one is ready, does not block
+    pub fn try_recv(&self) -> Option<Response> {
+        self.responses.try_recv().ok()
+    }
+}
+
+fn run(
+    config_path: Option<String>,
+    commands: mpsc::Receiver<Command>,
+    responses: mpsc::Sender<Response>,
+) {
+    // Loaded again after each command rather than on every check for changes
+    let mut config = config::get_or_create(config_path.as_deref()).ok();
+    let mut last_modified = modified(config.as_ref());
+    let mut replayed_at = Instant::now();
+    let mut hidden = Vec::new();
+    loop {
+        match commands.recv_timeout(WATCH_INTERVAL) {
+            Ok(Command::HideProject(project)) => hidden.push(project),
+            Ok(command) => handle(config_path.as_deref(), &hidden, command, &responses),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let now_modified = modified(config.as_ref());
+                if now_modified != last_modified {
+                    last_modified = now_modified;
+                    if let Ok(response) = project_names(config_path.as_deref()) {
+                        let _ = responses.send(response);
+                    }
+                    if responses.send(Response::Changed).is_err() {
+                        break;
+                    }
+                }
+                if replayed_at.elapsed() < REPLAY_INTERVAL {
+                    continue;
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+        // Our own changes are not news
+        config = config::get_or_create(config_path.as_deref()).ok();
+        last_modified = modified(config.as_ref());
+        replayed_at = Instant::now();
+
+        let pending = replay(config_path.as_deref());
+        if responses.send(Response::Pending(pending)).is_err() {
+            break;
+        }
+        if let Ok(response) = project_names(config_path.as_deref()) {
+            if responses.send(response).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// When the backend's source last changed, None for Todoist which has nothing to watch
+fn modified(config: Option<&config::Config>) -> Option<SystemTime> {
+    let config = config?;
+    match config.backend {
+        Backend::Todoist => None,
+        _ => config.backend.task_backend().modified(config),
+    }
+}
+
+fn project_names(config_path: Option<&str>) -> Result<Response, Error> {
+    let config = config::get_or_create(config_path)?;
+    Ok(Response::Projects {
+        all: projects::all_names(&config)?,
+        visible: projects::visible_names(&config)?,
+    })
+}
+
+/// Try to send the outbox, returns how many commands are still waiting
+fn replay(config_path: Option<&str>) -> usize {
+    match config::get_or_create(config_path) {
+        Ok(config) => outbox::flush(&config)
+            .or_else(|_| outbox::load(&config).map(|commands| commands.len()))
+            .unwrap_or_default(),
+        Err(_) => 0,
+    }
+}
+
+/// Config with the projects hidden in the GUI, which are not saved
+fn load(config_path: Option<&str>, hidden: &[String]) -> Result<config::Config, Error> {
+    let mut config = config::get_or_create(config_path)?;
+    config.hidden_projects = hidden.to_vec();
+    Ok(config)
+}
+
+fn handle(
+    config_path: Option<&str>,
+    hidden: &[String],
+    command: Command,
+    responses: &mpsc::Sender<Response>,
+) {
+    let (project, skipped, result) = match command {
+        Command::Next { project, skipped } => {
+            // Answer from the cache straight away, then again once synced
+            let cached = cached_next_item(config_path, hidden, &project, &skipped);
+            let sent_cached = cached.is_some();
+            if let Some(result) = cached {
+                send_next(responses, &project, result);
+            }
+
+            let result =
+                load(config_path, hidden).and_then(|c| projects::next_item(c, &project, &skipped));
+            match result {
+                // Keep showing the cached item while offline
+                Err(e) if sent_cached && e.is_transient() => {
+                    send_candidates(config_path, hidden, &project, &skipped, responses);
+                    return;
+                }
+                result => (project, skipped, result),
+            }
+        }
+        Command::Complete { project, skipped } => {
+            let result = update_then_next(config_path, hidden, &project, &skipped, |config| {
+                let id = config.next_id.clone();
+                let result = backend::complete_item(config)?;
+                if let Some(id) = id {
+                    let project = project.clone();
+                    let _ = responses.send(Response::Completed { project, id });
+                }
+                Ok(result)
+            });
+            (project, skipped, result)
+        }
+        Command::Add {
+            project,
+            text,
+            skipped,
+        } => {
+            let result = update_then_next(config_path, hidden, &project, &skipped, |config| {
+                backend::add_item(config, &project, &text)
+            });
+            (project, skipped, result)
+        }
+        Command::Undo {
+            project,
+            id,
+            skipped,
+        } => {
+            let result = update_then_next(config_path, hidden, &project, &skipped, |config| {
+                backend::uncomplete_item(config, &id)
+            });
+            (project, skipped, result)
+        }
+        Command::Snooze {
+            project,
+            snooze,
+            skipped,
+        } => {
+            let result = update_then_next(config_path, hidden, &project, &skipped, |config| {
+                backend::snooze_item(config, snooze)
+            });
+            (project, skipped, result)
+        }
+        Command::Promote {
+            project,
+            id,
+            skipped,
+        } => {
+            let result = load(config_path, hidden)
+                .and_then(|config| projects::promote_item(config, &project, &id, &skipped));
+            (project, skipped, result)
+        }
+        Command::Skip {
+            project,
+            mut skipped,
+        } => {
+            // The item on screen is the one saved as next_id, nothing is sent to Todoist
+            let result = load(config_path, hidden).and_then(|config| {
+                if let Some(id) = config.next_id.clone() {
+                    let _ = responses.send(Response::Skipped(id.clone()));
+                    skipped.push(id);
+                }
+                projects::cached_next_item(config, &project, &skipped)
+            });
+            (project, skipped, result)
+        }
+        Command::Refresh => {
+            let result =
+                load(config_path, hidden).and_then(|c| c.backend.task_backend().refresh(&c, None));
+            if let Err(e) = result {
+                let _ = responses.send(Response::Failed(e));
+            }
+            return;
+        }
+        // Kept by run for the commands that follow
+        Command::HideProject(_) => return,
+        Command::SelectProjects(names) => {
+            let result =
+                load(config_path, hidden).and_then(|c| c.set_visible_projects(names).save());
+            if let Err(e) = result {
+                let _ = responses.send(Response::Failed(e));
+            }
+            return;
+        }
+    };
+
+    send_next(responses, &project, result);
+    send_candidates(config_path, hidden, &project, &skipped, responses);
+}
+
+/// Change the current item, then fetch the next one, from the cache when offline
+fn update_then_next(
+    config_path: Option<&str>,
+    hidden: &[String],
+    project: &str,
+    skipped: &[String],
+    update: impl FnOnce(config::Config) -> Result<String, Error>,
+) -> Result<Option<String>, Error> {
+    load(config_path, hidden)
+        .and_then(update)
+        .and_then(|_| load(config_path, hidden))
+        .and_then(|c| match projects::next_item(c.clone(), project, skipped) {
+            Err(e) if e.is_transient() => projects::cached_next_item(c, project, skipped),
+            result => result,
+        })
+}
+
+/// The next item from the cache, or None if there hasn't been a sync to answer from
+fn cached_next_item(
+    config_path: Option<&str>,
+    hidden: &[String],
+    project: &str,
+    skipped: &[String],
+) -> Option<Result<Option<String>, Error>> {
+    let config = load(config_path, hidden).ok()?;
+    if !config.backend.task_backend().has_items(&config) {
+        return None;
+    }
+
+    Some(projects::cached_next_item(config, project, skipped))
+}
+
+/// Tell the GUI why the item was picked, there's nothing to explain if it couldn't be
+fn send_candidates(
+    config_path: Option<&str>,
+    hidden: &[String],
+    project: &str,
+    skipped: &[String],
+    responses: &mpsc::Sender<Response>,
+) {
+    let Ok(config) = load(config_path, hidden) else {
+        return;
+    };
+    let count = CANDIDATES.max(config.up_next + 1);
+    if let Ok(candidates) = projects::candidates(&config, project, skipped, count) {
+        let _ = responses.send(Response::Candidates {
+            project: project.to_owned(),
+            candidates,
+            up_next: config.up_next,
+        });
+    }
+}
+
+fn send_next(
+    responses: &mpsc::Sender<Response>,
+    project: &str,
+    result: Result<Option<String>, Error>,
+) {
+    // The receiver only goes away when the window is closed
+    let _ = responses.send(Response::Next {
+        project: project.to_owned(),
+        result,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::request;
+    use crate::stub_server::{self, StubServer};
+    use crate::test;
+    use pretty_assertions::assert_eq;
+
+    /// The next response that is not a pending count
+    fn next_response(worker: &Worker) -> (String, Result<Option<String>, Error>) {
+        loop {
+            match worker.responses.recv_timeout(Duration::from_secs(10)) {
+                Ok(Response::Next { project, result }) => return (project, result),
+                Ok(_) => (),
+                Err(e) => panic!("no response from worker: {e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn worker_answers_commands_in_the_background() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let worker = Worker::spawn(Some(config.path));
+
+        worker.send(Command::Next {
+            project: String::from("Home"),
+            skipped: Vec::new(),
+        });
+        let (project, result) = next_response(&worker);
+
+        assert_eq!(project, String::from("Home"));
+        assert!(result
+            .unwrap()
+            .unwrap()
+            .contains("Pay the electricity bill"));
+
+        worker.send(Command::Next {
+            project: String::from("Errands"),
+            skipped: Vec::new(),
+        });
+        let (_, result) = next_response(&worker);
+
+        assert_eq!(result, Err(Error::ProjectNotFound(String::from("Errands"))));
+    }
+
+    #[test]
+    fn worker_replays_the_outbox_and_reports_pending_commands() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let command = request::SyncCommand::new("item_close", serde_json::json!({"id": "1"}));
+        outbox::push(&config, command).unwrap();
+        let worker = Worker::spawn(Some(config.path.clone()));
+
+        worker.send(Command::Next {
+            project: String::from("Home"),
+            skipped: Vec::new(),
+        });
+        let (_, result) = next_response(&worker);
+        assert!(result.is_ok());
+
+        match worker.responses.recv_timeout(Duration::from_secs(10)) {
+            Ok(Response::Candidates { candidates, .. }) => {
+                let content: Vec<&str> = candidates.iter().map(|c| c.content.as_str()).collect();
+                assert_eq!(
+                    content,
+                    vec!["Pay the electricity bill", "Water the plants"]
+                );
+            }
+            response => panic!("expected candidates, got {response:?}"),
+        }
+        let pending = worker.responses.recv_timeout(Duration::from_secs(10));
+        assert_eq!(pending, Ok(Response::Pending(0)));
+        assert_eq!(outbox::load(&config), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn worker_discovers_and_selects_projects() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let worker = Worker::spawn(Some(config.path.clone()));
+        let all: Vec<String> = vec!["Home", "Inbox", "Work"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        worker.send(Command::Refresh);
+        assert_eq!(
+            next_projects(&worker),
+            Response::Projects {
+                all: all.clone(),
+                visible: all.clone()
+            }
+        );
+
+        worker.send(Command::SelectProjects(vec![String::from("Work")]));
+        assert_eq!(
+            next_projects(&worker),
+            Response::Projects {
+                all,
+                visible: vec![String::from("Work")]
+            }
+        );
+        let config = config::get_or_create(Some(&config.path)).unwrap();
+        assert_eq!(config.visible_projects, Some(vec![String::from("Work")]));
+    }
+
+    #[test]
+    fn worker_leaves_hidden_projects_out_of_all_projects() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let worker = Worker::spawn(Some(config.path.clone()));
+
+        worker.send(Command::HideProject(String::from("Home")));
+        worker.send(Command::Next {
+            project: String::from(projects::ALL_PROJECTS),
+            skipped: Vec::new(),
+        });
+        let (_, result) = next_response(&worker);
+        let text = result.unwrap().unwrap();
+        assert!(text.contains("Review the quarterly report"));
+    }
+
+    #[test]
+    fn worker_skips_without_changing_the_item() {
+        let server = StubServer::start(&stub_server::default_fixtures()).unwrap();
+        let config = test::helpers::config_with_stub(&server);
+        let worker = Worker::spawn(Some(config.path.clone()));
+
+        worker.send(Command::Next {
+            project: String::from("Home"),
+            skipped: Vec::new(),
+        });
+        let (_, result) = next_response(&worker);
+        assert!(result.is_ok());
+        let requests = server.requests().len();
+
+        worker.send(Command::Skip {
+            project: String::from("Home"),
+            skipped: Vec::new(),
+        });
+        let skipped = loop {
+            match worker.responses.recv_timeout(Duration::from_secs(10)) {
+                Ok(Response::Skipped(id)) => break id,
+                Ok(_) => (),
+                Err(e) => panic!("no response from worker: {e}"),
+            }
+        };
+        assert_eq!(skipped, String::from("6543210002"));
+
+        let (_, result) = next_response(&worker);
+        assert_eq!(result, Ok(Some(String::from("\nWater the plants"))));
+        assert_eq!(server.requests().len(), requests);
+        let config = config::get_or_create(Some(&config.path)).unwrap();
+        assert_eq!(config.next_id, Some(String::from("6543210001")));
+    }
+
+    fn next_projects(worker: &Worker) -> Response {
+        loop {
+            match worker.responses.recv_timeout(Duration::from_secs(10)) {
+                Ok(response @ Response::Projects { .. }) => return response,
+                Ok(Response::Failed(e)) => panic!("command failed: {e}"),
+                Ok(_) => (),
+                Err(e) => panic!("no response from worker: {e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn worker_reports_changes_to_a_watched_file() {
+        let config = test::helpers::save_to_temp_dir(test::helpers::config_fixture());
+        let path = crate::store::path(&config, "todo.txt");
+        std::fs::write(&path, "Water the plants +Home\n").unwrap();
+        let config = config::Config {
+            backend: Backend::TodoTxt {
+                path: path.display().to_string(),
+            },
+            ..config
+        };
+        config.clone().save().unwrap();
+        let worker = Worker::spawn(Some(config.path));
+        worker.send(Command::Refresh);
+        let pending = worker.responses.recv_timeout(Duration::from_secs(10));
+        assert_eq!(pending, Ok(Response::Pending(0)));
+
+        // Edited elsewhere, a minute later as far as the file system can tell
+        let file = std::fs::File::options().append(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        loop {
+            match worker.responses.recv_timeout(Duration::from_secs(10)) {
+                Ok(Response::Changed) => break,
+                Ok(_) => (),
+                Err(e) => panic!("no change reported: {e}"),
+            }
+        }
+    }
+}